@@ -0,0 +1,246 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+#[cfg(feature = "simd")]
+use pycniospores::force_kernel::{calc_force_from_bucket_simd, ForceKernel};
+use pycniospores::{
+    configuration::Interactions,
+    movement_calculator::{
+        calc_force_from_bucket, calc_forces, calculate_forces_on_spore, update_spores_with_forces,
+    },
+    spore::SporesState,
+    spore_mover::move_spores,
+    SimulationConfig, World,
+};
+
+// fixed seeds give the same spores on every machine, so results can be compared between commits
+const SEED: u64 = 42;
+// lets the spores clump together a bit, like in a running simulation
+const WARM_UP_TICKS: u32 = 20;
+
+// the grid is sized from the longest force reach
+fn world(nr_of_spores: u32, max_force_reach: f32, warm_up_ticks: u32) -> World {
+    let config = SimulationConfig {
+        seed: Some(SEED),
+        max_force_reach,
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, nr_of_spores);
+    world.step_n(warm_up_ticks);
+    world
+}
+
+fn forces_on_one_spore(c: &mut Criterion) {
+    let world = world(4096, 96.0, WARM_UP_TICKS);
+    let (config, spore_configs, state) = (world.config(), world.spore_configs(), world.state());
+    // the middle spore in storage order, somewhere in the middle of the universe
+    let index = state.len() / 2;
+    let (position, spore_type) = (state.positions[index], state.spore_types[index]);
+    let bucket = state.grid.get_bucket_from_pos(position);
+    let range = state.bucket_range(state.grid.bucket_index(bucket));
+
+    c.bench_function("calculate_forces_on_spore", |b| {
+        b.iter(|| {
+            calculate_forces_on_spore(config, spore_configs, position, spore_type, state, bucket)
+        })
+    });
+    c.bench_function("calc_force_from_bucket", |b| {
+        b.iter(|| {
+            calc_force_from_bucket(
+                config,
+                spore_configs,
+                position,
+                spore_type,
+                &state.positions[range.clone()],
+                &state.spore_types[range.clone()],
+            )
+        })
+    });
+}
+
+fn move_all_spores(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_spores");
+    group.sample_size(10);
+    for max_force_reach in [32.0, 64.0, 96.0] {
+        for nr_of_spores in [1_000, 4_096, 10_000] {
+            let world = world(nr_of_spores, max_force_reach, WARM_UP_TICKS);
+            group.bench_with_input(
+                BenchmarkId::new(format!("max_force_reach_{}", max_force_reach), nr_of_spores),
+                &world,
+                |b, world| {
+                    b.iter_batched(
+                        || world.state().clone(),
+                        |mut state| move_spores(world.config(), world.spore_configs(), &mut state),
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+// symmetric spore configurations are calculated once per pair of spores
+fn move_symmetric_spores(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_spores_symmetric");
+    group.sample_size(10);
+    for nr_of_spores in [1_000, 4_096, 10_000] {
+        let config = SimulationConfig {
+            seed: Some(SEED),
+            interactions: Interactions::Symmetric,
+            ..SimulationConfig::default()
+        };
+        let mut world = World::new(config, nr_of_spores);
+        world.step_n(WARM_UP_TICKS);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(nr_of_spores),
+            &world,
+            |b, world| {
+                b.iter_batched(
+                    || world.state().clone(),
+                    |mut state| move_spores(world.config(), world.spore_configs(), &mut state),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+// a full tick: forces, movement, sorting into buckets; compared with 487f35b in performance-tests.md
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.sample_size(10);
+    for nr_of_spores in [10_000, 50_000, 100_000] {
+        // no warm-up, like the baseline: a tick of the denser worlds takes seconds
+        let world = world(nr_of_spores, 96.0, 0);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(nr_of_spores),
+            &world,
+            |b, world| {
+                b.iter_batched(
+                    || {
+                        World::from_parts(
+                            world.config().clone(),
+                            world.spore_configs().clone(),
+                            world.state().clone(),
+                            world.tick(),
+                        )
+                    },
+                    |mut world| world.step(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bucket_migration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_into_buckets");
+    for nr_of_spores in [10_000, 50_000, 100_000] {
+        // no warm-up: a tick of the denser worlds takes seconds
+        let world = world(nr_of_spores, 96.0, 0);
+        let moved = moved_one_tick(&world);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(nr_of_spores),
+            &moved,
+            |b, moved| {
+                b.iter_batched(
+                    || moved.clone(),
+                    |mut state| state.sort_into_buckets(),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+// the spores after a tick of movement, before they are sorted into their new buckets
+fn moved_one_tick(world: &World) -> SporesState {
+    let mut state = world.state().clone();
+    let all_spores = 0..state.len();
+    let forces = calc_forces(
+        world.config(),
+        world.spore_configs(),
+        &state,
+        all_spores.clone(),
+    );
+    update_spores_with_forces(
+        world.config(),
+        world.spore_configs(),
+        &mut state,
+        all_spores,
+        &forces,
+    );
+    state
+}
+
+// `cargo bench --features simd`, compare with the scalar results above
+#[cfg(feature = "simd")]
+fn simd_kernel(c: &mut Criterion) {
+    let world = world(4096, 96.0, WARM_UP_TICKS);
+    let (config, spore_configs, state) = (world.config(), world.spore_configs(), world.state());
+    let index = state.len() / 2;
+    let (position, spore_type) = (state.positions[index], state.spore_types[index]);
+    let range = state.bucket_range(
+        state
+            .grid
+            .bucket_index(state.grid.get_bucket_from_pos(position)),
+    );
+    let (xs, ys): (Vec<f32>, Vec<f32>) = state.positions[range.clone()]
+        .iter()
+        .map(|position| (position.x, position.y))
+        .unzip();
+    c.bench_function("calc_force_from_bucket_simd", |b| {
+        b.iter(|| {
+            calc_force_from_bucket_simd(
+                config,
+                spore_configs,
+                position,
+                spore_type,
+                &xs,
+                &ys,
+                &state.spore_types[range.clone()],
+            )
+        })
+    });
+
+    let mut group = c.benchmark_group("move_spores_simd");
+    group.sample_size(10);
+    for nr_of_spores in [1_000, 4_096, 10_000] {
+        let config = SimulationConfig {
+            seed: Some(SEED),
+            force_kernel: ForceKernel::Simd,
+            ..SimulationConfig::default()
+        };
+        let mut world = World::new(config, nr_of_spores);
+        world.step_n(WARM_UP_TICKS);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(nr_of_spores),
+            &world,
+            |b, world| {
+                b.iter_batched(
+                    || world.state().clone(),
+                    |mut state| move_spores(world.config(), world.spore_configs(), &mut state),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    forces_on_one_spore,
+    move_all_spores,
+    move_symmetric_spores,
+    step,
+    bucket_migration
+);
+#[cfg(feature = "simd")]
+criterion_group!(simd_benches, simd_kernel);
+#[cfg(not(feature = "simd"))]
+criterion_main!(benches);
+#[cfg(feature = "simd")]
+criterion_main!(benches, simd_benches);
@@ -0,0 +1,33 @@
+use clap::{App, Arg};
+use pycniospores::{
+    cli::{get_recorder, get_run_limits, get_world, run_limit_args, world_args},
+    headless::run_headless,
+};
+use std::path::Path;
+
+#[global_allocator]
+static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+/// The simulation without a window: only needs the library, not ggez and its windowing stack.
+pub fn main() -> Result<(), String> {
+    let matches = App::new("Pycniospores headless")
+        .version("0.1")
+        .author("@wmclt on Gitlab")
+        .about("The large particle simulator, without a window")
+        .args(&world_args())
+        .arg(
+            Arg::with_name("snapshot")
+                .help("Sets the snapshot file to save the last tick to")
+                .long("snapshot")
+                .takes_value(true),
+        )
+        .args(&run_limit_args())
+        .get_matches();
+
+    let limits = get_run_limits(&matches)?;
+    let world = get_world(&matches)?;
+    let recorder = get_recorder(&matches, &world)?;
+    let snapshot_path = matches.value_of("snapshot").map(Path::new);
+    run_headless(world, &limits, snapshot_path, recorder);
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::{
+    configuration::SimulationConfig,
+    movement_calculator::to_calibrated_dist,
+    spore::SporesState,
+    thermostat::{split_mix, to_unit},
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// When two spores of `types` bond, how the bond pulls them together and when it breaks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BondRule {
+    /// The two spore types, in any order.
+    pub types: [u8; 2],
+    /// Two spores closer than `form_dist` bond with `form_probability` per tick.
+    pub form_dist: f32,
+    pub form_probability: f32,
+    /// The bond is a spring of `stiffness`, with a length of `rest_length` at rest.
+    pub rest_length: f32,
+    pub stiffness: f32,
+    /// It breaks when stretched beyond `break_stretch` times its rest length,
+    pub break_stretch: f32,
+    /// or when it is older than `max_age`, if set.
+    #[serde(default)]
+    pub max_age: Option<f32>,
+}
+
+impl BondRule {
+    pub fn is_valid(&self, number_of_configs: usize) -> bool {
+        self.types
+            .iter()
+            .all(|spore_type| (*spore_type as usize) < number_of_configs)
+            && self.form_dist > 0.0
+            && (0.0..=1.0).contains(&self.form_probability)
+            && self.rest_length > 0.0
+            && self.stiffness >= 0.0
+            && self.break_stretch > 1.0
+            && self.max_age.is_none_or(|max_age| max_age > 0.0)
+    }
+}
+
+/// A bond between two spores, by their ids: it survives the sorting of the spores into buckets.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bond {
+    pub ids: [u32; 2],
+    pub formed_at: f64,
+}
+
+/// The longest distance bonds form at, the buckets are at least as large.
+pub fn longest_form_dist(config: &SimulationConfig) -> f32 {
+    config
+        .bond_rules
+        .iter()
+        .fold(0.0, |max, rule| max.max(rule.form_dist))
+}
+
+// the index of the rule of every (own type, other type), like an `InteractionMatrix`
+struct RuleTable {
+    size: usize,
+    rules: Vec<Option<usize>>,
+}
+
+impl RuleTable {
+    fn new(config: &SimulationConfig) -> RuleTable {
+        let size = config.number_of_configs;
+        let mut rules = vec![None; size * size];
+        for (index, rule) in config.bond_rules.iter().enumerate() {
+            let [a, b] = rule.types.map(|spore_type| spore_type as usize);
+            rules[a * size + b] = Some(index);
+            rules[b * size + a] = Some(index);
+        }
+        RuleTable { size, rules }
+    }
+
+    fn get<'a>(&self, config: &'a SimulationConfig, types: [u8; 2]) -> Option<&'a BondRule> {
+        let [a, b] = types.map(|spore_type| spore_type as usize);
+        self.rules[a * self.size + b].map(|index| &config.bond_rules[index])
+    }
+}
+
+// the vector from the first spore to the second one, with their types
+fn bond_vector(
+    config: &SimulationConfig,
+    spores: &SporesState,
+    [a, b]: [usize; 2],
+) -> (Vector, f32, [u8; 2]) {
+    let dist = to_calibrated_dist(config, spores.positions[b], spores.positions[a]);
+    (
+        dist.vector,
+        dist.scalar,
+        [spores.spore_types[a], spores.spore_types[b]],
+    )
+}
+
+/// The spring forces of the bonds on all spores, in the order of the spores, empty without bonds.
+/// Summed bond after bond, so the result doesn't depend on the threads.
+pub fn bond_forces(config: &SimulationConfig, spores: &SporesState) -> Vec<Vector> {
+    if spores.bonds.is_empty() {
+        return Vec::new();
+    }
+    let (rules, indexes) = (RuleTable::new(config), spores.indexes_by_id());
+    let mut forces = vec![ZERO_VECTOR; spores.len()];
+    for bond in &spores.bonds {
+        let [a, b] = bond.ids.map(|id| indexes[id as usize]);
+        let (vector, length, types) = bond_vector(config, spores, [a, b]);
+        let rule = match rules.get(config, types) {
+            Some(rule) if length > 0.0 => rule,
+            _ => continue,
+        };
+        let force = vector * (rule.stiffness * (length - rule.rest_length) / length);
+        forces[a] += force;
+        forces[b] -= force;
+    }
+    forces
+}
+
+/// Breaks the bonds that are stretched too far or too old, or that lost a spore,
+/// then forms new bonds between the spores within reach. Call after sorting the spores into buckets.
+pub(crate) fn update_bonds(config: &SimulationConfig, spores: &mut SporesState) {
+    let (rules, indexes) = (RuleTable::new(config), spores.indexes_by_id());
+    let time = spores.time;
+    // a bond that breaks doesn't form again in the same tick
+    let mut bonded: HashSet<[u32; 2]> = spores.bonds.iter().map(|bond| bond.ids).collect();
+
+    let state = &*spores;
+    let kept: Vec<Bond> = state
+        .bonds
+        .iter()
+        .filter(|bond| {
+            let [a, b] = bond.ids.map(|id| indexes[id as usize]);
+            if a == usize::MAX || b == usize::MAX {
+                return false;
+            }
+            let (_, length, types) = bond_vector(config, state, [a, b]);
+            rules.get(config, types).is_some_and(|rule| {
+                length <= rule.rest_length * rule.break_stretch
+                    && rule
+                        .max_age
+                        .is_none_or(|max_age| time - bond.formed_at <= max_age as f64)
+            })
+        })
+        .copied()
+        .collect();
+
+    let mut nr_of_bonds = vec![0; state.len()];
+    for bond in &kept {
+        for id in bond.ids {
+            nr_of_bonds[indexes[id as usize]] += 1;
+        }
+    }
+
+    // the pairs that roll a bond, in a fixed order; the valences are checked afterwards
+    let (grid, rules) = (&state.grid, &rules);
+    let candidates: Vec<[usize; 2]> = (0..state.len())
+        .into_par_iter()
+        .flat_map_iter(|spore| {
+            let position = state.positions[spore];
+            grid.get_neighbors(grid.get_bucket_from_pos(position))
+                .flat_map(move |neighbor| state.bucket_range(grid.bucket_index(neighbor)))
+                .filter(move |other| *other > spore)
+                .filter(move |other| {
+                    let (_, dist, types) = bond_vector(config, state, [spore, *other]);
+                    rules.get(config, types).is_some_and(|rule| {
+                        dist < rule.form_dist
+                            && roll(config, 0, [state.ids[spore], state.ids[*other]], time)
+                                <= rule.form_probability as f64
+                    })
+                })
+                .map(move |other| [spore, other])
+        })
+        .collect();
+
+    let valence = |index: usize| {
+        config
+            .valences
+            .get(state.spore_types[index] as usize)
+            .map_or(u32::MAX, |valence| *valence)
+    };
+    let mut bonds = kept;
+    for [a, b] in candidates {
+        let ids = ordered([state.ids[a], state.ids[b]]);
+        if nr_of_bonds[a] < valence(a) && nr_of_bonds[b] < valence(b) && bonded.insert(ids) {
+            nr_of_bonds[a] += 1;
+            nr_of_bonds[b] += 1;
+            bonds.push(Bond {
+                ids,
+                formed_at: time,
+            });
+        }
+    }
+    spores.bonds = bonds;
+}
+
+fn ordered([a, b]: [u32; 2]) -> [u32; 2] {
+    [a.min(b), a.max(b)]
+}
+
+/// A uniform number in (0, 1] per pair of spores and time, like the thermal noise.
+/// A different `salt` gives independent numbers for the same pair, the bonds use 0.
+pub(crate) fn roll(config: &SimulationConfig, salt: u64, ids: [u32; 2], time: f64) -> f64 {
+    let [a, b] = ordered(ids);
+    to_unit(split_mix(
+        split_mix(config.seed.unwrap_or_default() ^ salt)
+            ^ split_mix(a as u64).rotate_left(16)
+            ^ split_mix(b as u64).rotate_left(32)
+            ^ split_mix(time.to_bits()).rotate_left(48),
+    ))
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::SimulationConfig,
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// What happens to a spore at the edges of the universe along an axis.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    /// The universe wraps around: a spore leaving at one edge comes back at the other one,
+    /// and feels the spores near the other edge.
+    Periodic,
+    /// A spore bounces off the edge: its speed across the edge is reversed.
+    Reflecting,
+    /// A spore leaving the universe is removed.
+    Absorbing,
+    /// A wall pushes the spores within `wall_reach` of the edge back, and reflects the spores
+    /// that hit the edge anyway.
+    SoftWall,
+}
+
+impl Boundary {
+    pub fn is_periodic(self) -> bool {
+        self == Boundary::Periodic
+    }
+
+    // the position and speed along the axis, brought back within 0..=universe_size
+    fn apply(self, position: f32, speed: f32, universe_size: f32) -> (f32, f32) {
+        match self {
+            Boundary::Periodic => (
+                ((position % universe_size) + universe_size) % universe_size,
+                speed,
+            ),
+            Boundary::Reflecting | Boundary::SoftWall if position < 0.0 => {
+                ((-position).min(universe_size), speed.abs())
+            }
+            Boundary::Reflecting | Boundary::SoftWall if position > universe_size => {
+                ((2.0 * universe_size - position).max(0.0), -speed.abs())
+            }
+            // the absorbed spores are removed at the end of the tick
+            Boundary::Reflecting | Boundary::SoftWall | Boundary::Absorbing => (position, speed),
+        }
+    }
+
+    // along the axis, towards the middle of the universe
+    fn wall_force(self, config: &SimulationConfig, position: f32, universe_size: f32) -> f32 {
+        if self != Boundary::SoftWall {
+            return 0.0;
+        }
+        let push = |dist_to_wall: f32| {
+            if dist_to_wall < config.wall_reach {
+                config.wall_amplitude * (1.0 - dist_to_wall.max(0.0) / config.wall_reach).powi(2)
+            } else {
+                0.0
+            }
+        };
+        push(position) - push(universe_size - position)
+    }
+
+    fn absorbs(self, position: f32, universe_size: f32) -> bool {
+        self == Boundary::Absorbing && !(0.0..=universe_size).contains(&position)
+    }
+}
+
+/// The boundary of each axis: `x` for the left and right edges, `y` for the top and bottom ones.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Boundaries {
+    pub x: Boundary,
+    pub y: Boundary,
+}
+
+impl Default for Boundaries {
+    fn default() -> Self {
+        Boundaries {
+            x: Boundary::Periodic,
+            y: Boundary::Periodic,
+        }
+    }
+}
+
+impl Boundaries {
+    pub fn has(&self, boundary: Boundary) -> bool {
+        self.x == boundary || self.y == boundary
+    }
+}
+
+/// The position and speed of a spore after it has moved, see `Boundary`.
+/// An absorbed spore keeps its position outside of the universe, until `is_absorbed` removes it.
+pub fn apply_boundaries(
+    config: &SimulationConfig,
+    position: Vector,
+    speed: Vector,
+) -> (Vector, Vector) {
+    let boundaries = config.boundaries;
+    let (x, speed_x) = boundaries
+        .x
+        .apply(position.x, speed.x, config.universe_width());
+    let (y, speed_y) = boundaries
+        .y
+        .apply(position.y, speed.y, config.universe_height());
+    (
+        Vector { x, y },
+        Vector {
+            x: speed_x,
+            y: speed_y,
+        },
+    )
+}
+
+/// The force of the soft walls on a spore.
+pub fn wall_force(config: &SimulationConfig, position: Vector) -> Vector {
+    let boundaries = config.boundaries;
+    if !boundaries.has(Boundary::SoftWall) {
+        return ZERO_VECTOR;
+    }
+    Vector {
+        x: boundaries
+            .x
+            .wall_force(config, position.x, config.universe_width()),
+        y: boundaries
+            .y
+            .wall_force(config, position.y, config.universe_height()),
+    }
+}
+
+/// Whether the spore has left the universe across an absorbing edge.
+pub fn is_absorbed(config: &SimulationConfig, position: Vector) -> bool {
+    let boundaries = config.boundaries;
+    boundaries.x.absorbs(position.x, config.universe_width())
+        || boundaries.y.absorbs(position.y, config.universe_height())
+}
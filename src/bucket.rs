@@ -1,52 +1,148 @@
-use std::usize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    configuration::{BUCKET_HEIGHT, BUCKET_WIDTH, NR_BUCKETS, NR_HORZ_BUCKETS, NR_VERT_BUCKETS},
-    vector::Vector,
+    bond::longest_form_dist, configuration::SimulationConfig, reaction::longest_reaction_dist,
+    spore::SporeConfigs, vector::Vector,
 };
 
 pub type BucketCoord = (usize, usize);
 
-// reading horizontally! => horizontal is interior loop
-pub fn get_buckets() -> Vec<BucketCoord> {
-    let mut buckets = Vec::with_capacity(NR_BUCKETS);
-    for vert in 0..NR_VERT_BUCKETS {
-        for horz in 0..NR_HORZ_BUCKETS {
-            buckets.push((horz, vert));
+// a tiny force reach shouldn't make for millions of empty buckets
+const MAX_BUCKETS_PER_AXIS: usize = 1024;
+
+/// The buckets the universe is divided in. A bucket is at least as large as the longest
+/// force reach, so the forces on a spore only come from its own and the neighboring buckets.
+/// Likewise for the distances bonds form and spores react at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Grid {
+    nr_horz_buckets: usize,
+    nr_vert_buckets: usize,
+    bucket_width: f32,
+    bucket_height: f32,
+    // whether the buckets at an edge neighbor those at the opposite edge, see `Boundary::Periodic`
+    horz_wraps: bool,
+    vert_wraps: bool,
+}
+
+impl Grid {
+    /// Buckets of about the longest force reach of the spore configurations,
+    /// or the longest distance bonds form or spores react at if that is longer.
+    pub fn new(config: &SimulationConfig, spore_configs: &SporeConfigs) -> Grid {
+        let max_force_reach = spore_configs
+            .force_reaches
+            .rows()
+            .flatten()
+            .fold(f32::MIN_POSITIVE, |max, reach| max.max(*reach))
+            .max(longest_form_dist(config))
+            .max(longest_reaction_dist(config));
+        let nr_of_buckets = |universe_size: f32| {
+            ((universe_size / max_force_reach).floor() as usize).clamp(1, MAX_BUCKETS_PER_AXIS)
+        };
+        let (nr_horz_buckets, nr_vert_buckets) = (
+            nr_of_buckets(config.universe_width()),
+            nr_of_buckets(config.universe_height()),
+        );
+
+        Grid {
+            nr_horz_buckets,
+            nr_vert_buckets,
+            bucket_width: config.universe_width() / nr_horz_buckets as f32,
+            bucket_height: config.universe_height() / nr_vert_buckets as f32,
+            horz_wraps: config.boundaries.x.is_periodic(),
+            vert_wraps: config.boundaries.y.is_periodic(),
         }
     }
-    buckets
-}
 
-pub fn get_bucket(x: f32, y: f32) -> BucketCoord {
-    (
-        (x / BUCKET_WIDTH as f32).floor() as usize,
-        (y / BUCKET_HEIGHT as f32).floor() as usize,
-    )
-}
+    pub fn nr_horz_buckets(&self) -> usize {
+        self.nr_horz_buckets
+    }
 
-pub fn get_bucket_from_pos(pos: Vector) -> BucketCoord {
-    get_bucket(pos.x, pos.y)
-}
+    pub fn nr_vert_buckets(&self) -> usize {
+        self.nr_vert_buckets
+    }
+
+    pub fn nr_buckets(&self) -> usize {
+        self.nr_horz_buckets * self.nr_vert_buckets
+    }
+
+    pub fn bucket_width(&self) -> f32 {
+        self.bucket_width
+    }
 
-pub fn get_neighbors(horz: isize, vert: isize) -> [BucketCoord; 9] {
-    [
-        (mod_horz(horz - 1), mod_vert(vert + 1)),
-        (mod_horz(horz), mod_vert(vert + 1)),
-        (mod_horz(horz + 1), mod_vert(vert + 1)),
-        (mod_horz(horz - 1), mod_vert(vert)),
-        (mod_horz(horz), mod_vert(vert)),
-        (mod_horz(horz + 1), mod_vert(vert)),
-        (mod_horz(horz - 1), mod_vert(vert - 1)),
-        (mod_horz(horz), mod_vert(vert - 1)),
-        (mod_horz(horz + 1), mod_vert(vert - 1)),
-    ]
+    pub fn bucket_height(&self) -> f32 {
+        self.bucket_height
+    }
+
+    pub fn get_bucket(&self, x: f32, y: f32) -> BucketCoord {
+        // a position just below the universe size can round up to the next bucket
+        (
+            usize::min(
+                (x / self.bucket_width).floor() as usize,
+                self.nr_horz_buckets - 1,
+            ),
+            usize::min(
+                (y / self.bucket_height).floor() as usize,
+                self.nr_vert_buckets - 1,
+            ),
+        )
+    }
+
+    pub fn get_bucket_from_pos(&self, pos: Vector) -> BucketCoord {
+        self.get_bucket(pos.x, pos.y)
+    }
+
+    /// The buckets are numbered in reading order: left to right, then top to bottom.
+    pub fn bucket_index(&self, (horz, vert): BucketCoord) -> usize {
+        vert * self.nr_horz_buckets + horz
+    }
+
+    pub fn bucket_coord(&self, bucket_index: usize) -> BucketCoord {
+        (
+            bucket_index % self.nr_horz_buckets,
+            bucket_index / self.nr_horz_buckets,
+        )
+    }
+
+    /// The bucket itself and its neighbors, every bucket once,
+    /// also when the universe is only one or two buckets wide or high.
+    /// Only wraps around at the periodic edges.
+    pub fn get_neighbors(&self, (horz, vert): BucketCoord) -> impl Iterator<Item = BucketCoord> {
+        let (nr_horz_buckets, nr_vert_buckets) = (self.nr_horz_buckets, self.nr_vert_buckets);
+        let (horz_wraps, vert_wraps) = (self.horz_wraps, self.vert_wraps);
+        neighbor_offsets(nr_vert_buckets, vert_wraps, true)
+            .iter()
+            .filter_map(move |vert_offset| {
+                neighbor(vert, *vert_offset, nr_vert_buckets, vert_wraps)
+            })
+            .flat_map(move |vert| {
+                neighbor_offsets(nr_horz_buckets, horz_wraps, false)
+                    .iter()
+                    .filter_map(move |horz_offset| {
+                        neighbor(horz, *horz_offset, nr_horz_buckets, horz_wraps)
+                    })
+                    .map(move |horz| (horz, vert))
+            })
+    }
 }
 
-fn mod_horz(horz: isize) -> usize {
-    (horz + NR_HORZ_BUCKETS as isize) as usize % NR_HORZ_BUCKETS
+// with fewer than three buckets that wrap around, the buckets before and after are the same one
+fn neighbor_offsets(nr_of_buckets: usize, wraps: bool, reversed: bool) -> &'static [isize] {
+    match (nr_of_buckets, wraps, reversed) {
+        (1, true, _) => &[0],
+        (2, true, _) => &[0, 1],
+        (_, _, false) => &[-1, 0, 1],
+        (_, _, true) => &[1, 0, -1],
+    }
 }
 
-fn mod_vert(vert: isize) -> usize {
-    (vert + NR_VERT_BUCKETS as isize) as usize % NR_VERT_BUCKETS
+// no neighbor beyond an edge that doesn't wrap around
+fn neighbor(index: usize, offset: isize, nr_of_buckets: usize, wraps: bool) -> Option<usize> {
+    let neighbor = index as isize + offset;
+    if wraps {
+        Some((neighbor + nr_of_buckets as isize) as usize % nr_of_buckets)
+    } else {
+        (0..nr_of_buckets as isize)
+            .contains(&neighbor)
+            .then_some(neighbor as usize)
+    }
 }
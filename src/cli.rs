@@ -0,0 +1,123 @@
+use crate::{
+    headless::RunLimits, snapshot::load_snapshot, trajectory::TrajectoryRecorder, SimulationConfig,
+    World,
+};
+use clap::{value_t, Arg, ArgMatches};
+use std::{path::Path, time::Duration};
+
+// the command line of the viewer and of the headless binary, so they take the same options
+
+const DEFAULT_RECORD_INTERVAL: &str = "10";
+
+/// The options that set up the world and its recording.
+pub fn world_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("number")
+            .help("Sets the number of particles ('spores')")
+            .short("n")
+            .long("number")
+            .takes_value(true),
+        Arg::with_name("config")
+            .help("Sets the TOML configuration file, missing fields keep their default value")
+            .short("c")
+            .long("config")
+            .takes_value(true),
+        Arg::with_name("seed")
+            .help("Seeds the random generation of the spore configurations and the spores")
+            .short("s")
+            .long("seed")
+            .takes_value(true),
+        Arg::with_name("resume")
+            .help("Resumes the simulation saved in a snapshot file")
+            .short("r")
+            .long("resume")
+            .takes_value(true)
+            .conflicts_with_all(&["number", "config", "seed"]),
+        Arg::with_name("record")
+            .help("Records the positions of the spores to a compressed trajectory file")
+            .long("record")
+            .takes_value(true),
+        Arg::with_name("record-every")
+            .help("Sets the number of ticks between two recorded frames")
+            .long("record-every")
+            .takes_value(true)
+            .default_value(DEFAULT_RECORD_INTERVAL),
+    ]
+}
+
+/// The options that stop a headless run, see `get_run_limits`.
+pub fn run_limit_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("ticks")
+            .help("Stops a headless run after this number of ticks")
+            .long("ticks")
+            .takes_value(true),
+        Arg::with_name("duration")
+            .help("Stops a headless run after this number of seconds")
+            .long("duration")
+            .takes_value(true),
+    ]
+}
+
+/// The limits of a headless run, at least one of them is needed.
+pub fn get_run_limits(matches: &ArgMatches) -> Result<RunLimits, String> {
+    let limits = RunLimits {
+        max_ticks: matches
+            .value_of("ticks")
+            .map(|_| value_t!(matches, "ticks", u32).unwrap_or_else(|e| e.exit())),
+        max_duration: matches
+            .value_of("duration")
+            .map(|_| {
+                let secs = value_t!(matches, "duration", f32).unwrap_or_else(|e| e.exit());
+                Duration::try_from_secs_f32(secs).map_err(|err| format!("--duration: {}", err))
+            })
+            .transpose()?,
+    };
+    if limits.max_ticks.is_none() && limits.max_duration.is_none() {
+        return Err("a headless run needs --ticks and/or --duration".to_string());
+    }
+    Ok(limits)
+}
+
+/// The resumed snapshot, or a new world from the configuration file, the seed and the number of spores.
+pub fn get_world(matches: &ArgMatches) -> Result<World, String> {
+    if let Some(path) = matches.value_of("resume") {
+        return load_snapshot(Path::new(path)).map_err(|err| format!("{}: {}", path, err));
+    }
+
+    let mut config = get_config(matches)?;
+    if matches.is_present("seed") {
+        config.seed = Some(value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit()));
+    }
+    let nr_of_spores = matches
+        .value_of("number")
+        .map(|_| value_t!(matches, "number", u32).unwrap_or_else(|e| e.exit()))
+        .unwrap_or(config.number_of_spores);
+    Ok(World::new(config, nr_of_spores))
+}
+
+pub fn get_recorder(
+    matches: &ArgMatches,
+    world: &World,
+) -> Result<Option<TrajectoryRecorder>, String> {
+    let Some(path) = matches.value_of("record") else {
+        return Ok(None);
+    };
+    let record_interval = value_t!(matches, "record-every", u32).unwrap_or_else(|e| e.exit());
+    if record_interval == 0 {
+        return Err("--record-every must be positive".to_string());
+    }
+    TrajectoryRecorder::create(Path::new(path), world, record_interval)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path, err))
+}
+
+/// The configuration file, or the default configuration without one.
+pub fn get_config(matches: &ArgMatches) -> Result<SimulationConfig, String> {
+    match matches.value_of("config") {
+        Some(path) => {
+            SimulationConfig::load(Path::new(path)).map_err(|err| format!("{}: {}", path, err))
+        }
+        None => Ok(SimulationConfig::default()),
+    }
+}
@@ -1,33 +1,384 @@
-// TODO put in config file
-
-// DEFAULT WINDOW DIMENSIONS
-pub const WINDOW_HEIGHT: f32 = 800.0;
-pub const WINDOW_WIDTH: f32 = 1280.0;
-
-// UNIVERSE
-// 64 = 40 * 1.6 -> 1.6 = SCREEN_RATIO
-pub const UNIVERSE_SCALE_FACTOR: f32 = 2.0;
-pub const UNIVERSE_WIDTH: f32 = 2560.0 * UNIVERSE_SCALE_FACTOR;
-pub const UNIVERSE_HEIGHT: f32 = 1600.0 * UNIVERSE_SCALE_FACTOR;
-
-// SPORE CONFIGURATIONS
-pub const USE_PREVIOUS_CONFIGURATIONS: bool = false;
-
-// SPORES
-pub const MAX_REPULSION_DIST: f32 = 24.0;
-pub const REPULSION_AMPLITUDE: f32 = -5.0 * MAX_FORCE_AMPLITUDE;
-pub const MAX_FORCE_AMPLITUDE: f32 = 0.15;
-pub const MAX_FORCE_REACH: f32 = 64.0 * 1.5;
+use crate::{
+    bond::BondRule,
+    boundary::{Boundaries, Boundary},
+    field::{FieldConfig, FieldShapeConfig, Gradient},
+    force_kernel::ForceKernel,
+    force_law::ForceLawKind,
+    generators::{shortest_force_reach, PREVIOUS_NUMBER_OF_CONFIGS},
+    integrator::Integrator,
+    obstacle::{Mask, ObstacleConfig},
+    reaction::{longest_reaction_dist, ReactionRule},
+    spore::SporeConfigs,
+    thermostat::Thermostat,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt, fs, path::Path};
+
 // SPORE CONFIGS
-pub const NUMBER_OF_CONFIGS: usize = 9;
-pub const NUMBER_OF_SPORES: u16 = 4096; // Can go up to 10k on a 2016 MacBook Pro
-
-pub const FRICTION: f32 = 0.94; // friction should be low!
-
-// BUCKETS
-pub const BUCKET_SCALE_FACTOR: usize = 4;
-pub const NR_VERT_BUCKETS: usize = 40 / BUCKET_SCALE_FACTOR; // 10
-pub const NR_HORZ_BUCKETS: usize = 64 / BUCKET_SCALE_FACTOR; // 16
-pub const BUCKET_HEIGHT: usize = UNIVERSE_HEIGHT as usize / NR_VERT_BUCKETS;
-pub const BUCKET_WIDTH: usize = UNIVERSE_WIDTH as usize / NR_HORZ_BUCKETS;
-pub const NR_BUCKETS: usize = NR_HORZ_BUCKETS * NR_VERT_BUCKETS; // 10*16 = 160
+// spore types are stored as u8
+pub const MAX_NUMBER_OF_CONFIGS: usize = u8::MAX as usize + 1;
+
+/// All the knobs of a run. Loaded from a TOML file, every missing field falls back to its default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    // DEFAULT WINDOW DIMENSIONS
+    pub window_height: f32,
+    pub window_width: f32,
+
+    // UNIVERSE
+    // 64 = 40 * 1.6 -> 1.6 = SCREEN_RATIO
+    pub universe_scale_factor: f32,
+
+    // RANDOMNESS
+    // the same seed gives the same spore configurations, spores and therefore the same run
+    pub seed: Option<u64>,
+
+    // SPORE CONFIGURATIONS
+    pub use_previous_configurations: bool,
+    pub interactions: Interactions,
+    pub number_of_configs: usize,
+    // used instead of random configurations, as printed at the start of a run
+    pub spore_configs: Option<SporeConfigs>,
+    pub number_of_spores: u32, // Can go up to 10k on a 2016 MacBook Pro
+    // per spore type, empty for the masses (1.0) and radii (0.0) of the spore configurations
+    pub masses: Vec<f32>,
+    pub radii: Vec<f32>,
+
+    // SPORES
+    pub max_repulsion_dist: f32,
+    pub max_force_amplitude: f32,
+    pub repulsion_amplitude: f32,
+    pub max_force_reach: f32,
+    pub force_law: ForceLawKind,
+    pub force_kernel: ForceKernel,
+    pub friction: f32, // friction should be low!
+
+    // BOUNDARIES
+    pub boundaries: Boundaries,
+    // the soft walls push the spores within wall_reach of the edge back, up to wall_amplitude at the edge
+    pub wall_reach: f32,
+    pub wall_amplitude: f32,
+    // static obstacles the spores bounce off
+    pub obstacles: Vec<ObstacleConfig>,
+
+    // EXTERNAL FIELDS
+    // forces on every spore that only depend on its position and the time
+    pub fields: Vec<FieldConfig>,
+
+    // INTEGRATION
+    pub update_mode: UpdateMode,
+    pub integrator: Integrator,
+    // the time covered by a tick, the friction is the damping over a time of 1.0
+    pub dt: f32,
+
+    // THERMOSTAT
+    // thermal noise against freezing into fixed clusters, see Thermostat
+    pub thermostat: Thermostat,
+
+    // BONDS
+    // springs between spores that form and break by these rules, see BondRule
+    pub bond_rules: Vec<BondRule>,
+    // the most bonds a spore can have per spore type, empty for no limit
+    pub valences: Vec<u32>,
+
+    // REACTIONS
+    // spores that stay close together change their types by these rules, see ReactionRule
+    pub reactions: Vec<ReactionRule>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        let max_force_amplitude = 0.15;
+        SimulationConfig {
+            window_height: 800.0,
+            window_width: 1280.0,
+            universe_scale_factor: 2.0,
+            seed: None,
+            use_previous_configurations: false,
+            interactions: Interactions::Pairwise,
+            number_of_configs: 9,
+            spore_configs: None,
+            number_of_spores: 4096,
+            masses: Vec::new(),
+            radii: Vec::new(),
+            max_repulsion_dist: 24.0,
+            max_force_amplitude,
+            repulsion_amplitude: -5.0 * max_force_amplitude,
+            max_force_reach: 64.0 * 1.5,
+            force_law: ForceLawKind::Triangle,
+            force_kernel: ForceKernel::Scalar,
+            friction: 0.94,
+            boundaries: Boundaries::default(),
+            wall_reach: 24.0,
+            wall_amplitude: 0.75,
+            obstacles: Vec::new(),
+            fields: Vec::new(),
+            update_mode: UpdateMode::Synchronous,
+            integrator: Integrator::SemiImplicitEuler,
+            dt: 1.0,
+            thermostat: Thermostat::Off,
+            bond_rules: Vec::new(),
+            valences: Vec::new(),
+            reactions: Vec::new(),
+        }
+    }
+}
+
+/// How the random spore configurations are generated.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interactions {
+    /// Every (own type, other type) pair gets its own force.
+    Pairwise,
+    /// The force only depends on the other spore's type, like before the interaction matrix.
+    ByOtherType,
+    /// Every pair of types gets a single force, the same in both directions.
+    /// The forces are then calculated once per pair of spores, see `calc_forces`.
+    Symmetric,
+}
+
+/// How the spores of the different buckets are moved within a tick.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateMode {
+    /// All forces are calculated from the state at the start of the tick, then applied.
+    Synchronous,
+    /// The buckets are moved one after another, like before the synchronous update:
+    /// the later buckets already feel the moved spores of the earlier ones.
+    Sequential,
+}
+
+impl SimulationConfig {
+    pub fn load(path: &Path) -> Result<SimulationConfig, ConfigError> {
+        let content = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let config: SimulationConfig = toml::from_str(&content).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        // the images are loaded again for every world, this only fails early
+        for obstacle in &config.obstacles {
+            if let ObstacleConfig::Mask { path } = obstacle {
+                Mask::load(&config, Path::new(path)).map_err(|err| {
+                    ConfigError::Invalid(format!("cannot load mask {}: {}", path, err))
+                })?;
+            }
+        }
+        for field in &config.fields {
+            if let FieldShapeConfig::Gradient { path, strength } = &field.shape {
+                Gradient::load(&config, Path::new(path), *strength).map_err(|err| {
+                    ConfigError::Invalid(format!("cannot load gradient {}: {}", path, err))
+                })?;
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: &str| Err(ConfigError::Invalid(message.to_string()));
+
+        if self.window_width <= 0.0 || self.window_height <= 0.0 {
+            return invalid("window dimensions must be positive");
+        }
+        if self.universe_scale_factor <= 0.0 {
+            return invalid("universe_scale_factor must be positive");
+        }
+        if self.number_of_configs == 0 || self.number_of_configs > MAX_NUMBER_OF_CONFIGS {
+            return invalid(&format!(
+                "number_of_configs must be between 1 and {}",
+                MAX_NUMBER_OF_CONFIGS
+            ));
+        }
+        if self.use_previous_configurations && self.number_of_configs != PREVIOUS_NUMBER_OF_CONFIGS
+        {
+            return invalid(&format!(
+                "the previous configurations need number_of_configs = {}",
+                PREVIOUS_NUMBER_OF_CONFIGS
+            ));
+        }
+        if self.max_repulsion_dist <= 0.0 || self.max_force_reach <= 0.0 {
+            return invalid("max_repulsion_dist and max_force_reach must be positive");
+        }
+        if let Some(spore_configs) = &self.spore_configs {
+            if spore_configs.number_of_configs() != self.number_of_configs
+                || spore_configs.force_factors.size() != self.number_of_configs
+                || spore_configs.force_reaches.size() != self.number_of_configs
+            {
+                return invalid("the spore_configs matrices must have number_of_configs rows");
+            }
+            if spore_configs
+                .force_reaches
+                .rows()
+                .flatten()
+                .any(|reach| *reach <= 0.0 || *reach > self.max_force_reach)
+            {
+                return invalid("the force_reaches must be between 0.0 and max_force_reach");
+            }
+            if spore_configs
+                .repulsion_dists
+                .rows()
+                .flatten()
+                .any(|dist| *dist <= 0.0)
+            {
+                return invalid("the repulsion_dists must be positive");
+            }
+        }
+        let (pasted_masses, pasted_radii): (&[f32], &[f32]) = match &self.spore_configs {
+            Some(spore_configs) => (&spore_configs.masses, &spore_configs.radii),
+            None => (&[], &[]),
+        };
+        let (masses, radii) = (
+            if self.masses.is_empty() {
+                pasted_masses
+            } else {
+                &self.masses
+            },
+            if self.radii.is_empty() {
+                pasted_radii
+            } else {
+                &self.radii
+            },
+        );
+        if [&self.masses[..], &self.radii, pasted_masses, pasted_radii]
+            .iter()
+            .any(|values| !values.is_empty() && values.len() != self.number_of_configs)
+        {
+            return invalid("the masses and radii need a value per spore type");
+        }
+        if masses.iter().any(|mass| *mass <= 0.0) || radii.iter().any(|radius| *radius < 0.0) {
+            return invalid("the masses must be positive and the radii at least 0.0");
+        }
+        // the repulsion of two touching spores has to be within their reach, see `apply_radii`
+        let largest_radius = radii.iter().fold(0.0, |max: f32, radius| max.max(*radius));
+        let shortest_reach = shortest_force_reach(self);
+        if 2.0 * largest_radius >= shortest_reach {
+            return invalid(&format!(
+                "two spores touching must be within the shortest force reach {:.2}: \
+                 make the radii smaller or max_force_reach longer",
+                shortest_reach
+            ));
+        }
+        match self.force_law {
+            ForceLawKind::Morse { width } if width <= 0.0 => {
+                return invalid("the width of the morse force law must be positive");
+            }
+            ForceLawKind::InversePower { exponent } if exponent <= 0.0 => {
+                return invalid("the exponent of the inverse power force law must be positive");
+            }
+            _ => {}
+        }
+        if !(0.0..=1.0).contains(&self.friction) {
+            return invalid("friction must be between 0.0 and 1.0");
+        }
+        if !(self.dt > 0.0 && self.dt.is_finite()) {
+            return invalid("dt must be positive");
+        }
+        // these integrators damp the speed continuously, which needs some speed to be left
+        if !self.integrator.evaluates_forces_once()
+            && self.thermostat == Thermostat::Off
+            && self.friction == 0.0
+        {
+            return invalid("velocity_verlet and rk4 need a friction above 0.0");
+        }
+        if self.update_mode == UpdateMode::Sequential && !self.integrator.evaluates_forces_once() {
+            return invalid("the sequential update mode only works with the euler integrators");
+        }
+        if !self.thermostat.is_valid() {
+            return invalid(
+                "the langevin thermostat needs a positive damping, and temperatures of at least 0.0",
+            );
+        }
+        if self.boundaries.has(Boundary::SoftWall)
+            && !(self.wall_reach > 0.0 && self.wall_amplitude >= 0.0)
+        {
+            return invalid("soft walls need a positive wall_reach and wall_amplitude");
+        }
+        let invalid_obstacle = self.obstacles.iter().any(|obstacle| match obstacle {
+            ObstacleConfig::Circle { radius, .. } => *radius <= 0.0,
+            ObstacleConfig::Rectangle { min, max } => min.x >= max.x || min.y >= max.y,
+            ObstacleConfig::Segment { thickness, .. } => *thickness <= 0.0,
+            ObstacleConfig::Mask { .. } => false,
+        });
+        if invalid_obstacle {
+            return invalid(
+                "obstacles need a positive radius or thickness, and a rectangle min below its max",
+            );
+        }
+        if self.fields.iter().any(|field| !field.modulation.is_valid()) {
+            return invalid(
+                "field modulations need a positive period or duration, and a duty between 0.0 and 1.0",
+            );
+        }
+        // a spore only feels the nearest copy of another one across the wrap-around
+        if self.max_force_reach > self.universe_width().min(self.universe_height()) / 2.0 {
+            return invalid("max_force_reach must not exceed half of the universe");
+        }
+        if !self
+            .bond_rules
+            .iter()
+            .all(|rule| rule.is_valid(self.number_of_configs))
+        {
+            return invalid(
+                "bond rules need existing spore types, a positive form_dist, rest_length and max_age, \
+                 a form_probability between 0.0 and 1.0, a stiffness of at least 0.0 and a break_stretch above 1.0",
+            );
+        }
+        // in either order: a later rule would silently replace an earlier one
+        let mut bonded_types = HashSet::new();
+        if !self.bond_rules.iter().all(|rule| {
+            let [a, b] = rule.types;
+            bonded_types.insert([a.min(b), a.max(b)])
+        }) {
+            return invalid("two bond rules must not have the same pair of spore types");
+        }
+        let longest_bond = self.bond_rules.iter().fold(0.0, |max: f32, rule| {
+            max.max(rule.form_dist)
+                .max(rule.rest_length * rule.break_stretch)
+        });
+        if longest_bond > self.universe_width().min(self.universe_height()) / 2.0 {
+            return invalid("bonds must not reach beyond half of the universe");
+        }
+        if !(self.valences.is_empty() || self.valences.len() == self.number_of_configs) {
+            return invalid("the valences need a value per spore type");
+        }
+        if !self
+            .reactions
+            .iter()
+            .all(|rule| rule.is_valid(self.number_of_configs))
+        {
+            return invalid(
+                "reactions need existing spore types, one or two products, a positive dist, \
+                 a contact_time of at least 0.0 and a probability between 0.0 and 1.0",
+            );
+        }
+        if longest_reaction_dist(self) > self.universe_width().min(self.universe_height()) / 2.0 {
+            return invalid("reactions must not reach beyond half of the universe");
+        }
+        Ok(())
+    }
+
+    pub fn universe_width(&self) -> f32 {
+        2560.0 * self.universe_scale_factor
+    }
+
+    pub fn universe_height(&self) -> f32 {
+        1600.0 * self.universe_scale_factor
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "cannot read configuration file: {}", err),
+            ConfigError::Parse(err) => write!(f, "cannot parse configuration file: {}", err),
+            ConfigError::Invalid(message) => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
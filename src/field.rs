@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::TAU, path::Path};
+
+use crate::{
+    configuration::SimulationConfig,
+    image::{GrayImage, ImageError},
+    obstacle::normalized,
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// A force on every spore that depends on its position and the time, not on the other spores.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldConfig {
+    pub shape: FieldShapeConfig,
+    #[serde(default)]
+    pub modulation: Modulation,
+}
+
+/// The force of a field at full strength, in the coordinates of the universe.
+/// Fields don't wrap around the edges of the universe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum FieldShapeConfig {
+    /// The same force everywhere, like gravity or a drift.
+    Uniform { force: Vector },
+    /// Towards `center` with the same strength everywhere, away from it with a negative strength.
+    Radial { center: Vector, strength: f32 },
+    /// Around `center`: clockwise on the screen with a positive strength.
+    Vortex { center: Vector, strength: f32 },
+    /// Along x, from `-strength` at the top to `strength` at the bottom of the universe,
+    /// like a flow between two plates moving in opposite directions.
+    Shear { strength: f32 },
+    /// Down the brightness of a PNG stretched over the whole universe, as if it was a landscape:
+    /// the spores are pushed from the bright pixels to the dark ones, with `strength` where a
+    /// pixel goes from white to black.
+    Gradient { path: String, strength: f32 },
+}
+
+/// How the strength of a field changes over time, as a factor of its full strength.
+/// The time is the number of ticks times `dt`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Modulation {
+    /// Always at full strength.
+    #[default]
+    Constant,
+    /// `sin(2π time / period + phase)`: the field reverses every half period.
+    Sine { period: f32, phase: f32 },
+    /// At full strength for the first `duty` part of every period, off for the rest.
+    Pulse { period: f32, duty: f32 },
+    /// From off to full strength over `duration`, then at full strength.
+    Ramp { duration: f32 },
+}
+
+impl Modulation {
+    pub fn factor(self, time: f64) -> f32 {
+        match self {
+            Modulation::Constant => 1.0,
+            Modulation::Sine { period, phase } => {
+                // in f64, a long run would lose the phase in f32
+                (TAU * time / period as f64 + phase as f64).sin() as f32
+            }
+            Modulation::Pulse { period, duty } => {
+                let part_of_period = (time / period as f64).fract();
+                if part_of_period < duty as f64 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Modulation::Ramp { duration } => (time / duration as f64).min(1.0) as f32,
+        }
+    }
+
+    pub fn is_valid(self) -> bool {
+        match self {
+            Modulation::Constant => true,
+            Modulation::Sine { period, phase } => period > 0.0 && phase.is_finite(),
+            Modulation::Pulse { period, duty } => period > 0.0 && (0.0..=1.0).contains(&duty),
+            Modulation::Ramp { duration } => duration > 0.0,
+        }
+    }
+}
+
+/// A field of the run, see `FieldShapeConfig`. A gradient holds the brightness of its image
+/// instead of its file, so a snapshot doesn't need the file anymore.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldShape {
+    Uniform { force: Vector },
+    Radial { center: Vector, strength: f32 },
+    Vortex { center: Vector, strength: f32 },
+    Shear { strength: f32 },
+    Gradient(Gradient),
+}
+
+/// The brightness of the pixels of an image between 0.0 (black) and 1.0 (white), row by row
+/// from the top left. Transparent pixels are white.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    pub width: usize,
+    pub height: usize,
+    pub brightness: Vec<f32>,
+    pub strength: f32,
+    // the size of a pixel in the universe
+    pub pixel_width: f32,
+    pub pixel_height: f32,
+}
+
+impl Gradient {
+    /// Loads a PNG and stretches it over the universe.
+    pub fn load(
+        config: &SimulationConfig,
+        path: &Path,
+        strength: f32,
+    ) -> Result<Gradient, ImageError> {
+        let image = GrayImage::load(path)?;
+        let (width, height) = (image.width, image.height);
+        Ok(Gradient {
+            width,
+            height,
+            brightness: image
+                .pixels
+                .iter()
+                .map(|(luma, alpha)| 1.0 - (1.0 - *luma as f32 / 255.0) * (*alpha as f32 / 255.0))
+                .collect(),
+            strength,
+            pixel_width: config.universe_width() / width as f32,
+            pixel_height: config.universe_height() / height as f32,
+        })
+    }
+
+    // the differences with the neighboring pixels, one sided at the edges of the image
+    fn force(&self, position: Vector) -> Vector {
+        let clamp = |value: f32, size: usize| (value.max(0.0) as usize).min(size - 1);
+        let (x, y) = (
+            clamp(position.x / self.pixel_width, self.width),
+            clamp(position.y / self.pixel_height, self.height),
+        );
+        let brightness = |x: usize, y: usize| self.brightness[y * self.width + x];
+        let slope = |before: usize, after: usize, at: &dyn Fn(usize) -> f32| {
+            if after == before {
+                0.0
+            } else {
+                (at(after) - at(before)) / (after - before) as f32
+            }
+        };
+        let slope_x = slope(x.saturating_sub(1), (x + 1).min(self.width - 1), &|x| {
+            brightness(x, y)
+        });
+        let slope_y = slope(y.saturating_sub(1), (y + 1).min(self.height - 1), &|y| {
+            brightness(x, y)
+        });
+        Vector {
+            x: -slope_x,
+            y: -slope_y,
+        } * self.strength
+    }
+}
+
+impl FieldShape {
+    fn force(&self, config: &SimulationConfig, position: Vector) -> Vector {
+        match self {
+            FieldShape::Uniform { force } => *force,
+            FieldShape::Radial { center, strength } => normalized(*center - position) * *strength,
+            FieldShape::Vortex { center, strength } => {
+                let outward = normalized(position - *center);
+                Vector {
+                    x: -outward.y,
+                    y: outward.x,
+                } * *strength
+            }
+            FieldShape::Shear { strength } => {
+                let half_height = config.universe_height() / 2.0;
+                Vector {
+                    x: strength * (position.y - half_height) / half_height,
+                    y: 0.0,
+                }
+            }
+            FieldShape::Gradient(gradient) => gradient.force(position),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub shape: FieldShape,
+    pub modulation: Modulation,
+}
+
+/// The fields of a run, their forces are added to the forces between the spores.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fields {
+    pub fields: Vec<Field>,
+}
+
+impl Fields {
+    /// The fields of the configuration, with the gradients loaded from their files.
+    pub fn load(config: &SimulationConfig) -> Result<Fields, ImageError> {
+        let fields = config
+            .fields
+            .iter()
+            .map(|field| {
+                let shape = match field.shape.clone() {
+                    FieldShapeConfig::Uniform { force } => FieldShape::Uniform { force },
+                    FieldShapeConfig::Radial { center, strength } => {
+                        FieldShape::Radial { center, strength }
+                    }
+                    FieldShapeConfig::Vortex { center, strength } => {
+                        FieldShape::Vortex { center, strength }
+                    }
+                    FieldShapeConfig::Shear { strength } => FieldShape::Shear { strength },
+                    FieldShapeConfig::Gradient { path, strength } => {
+                        FieldShape::Gradient(Gradient::load(config, Path::new(&path), strength)?)
+                    }
+                };
+                Ok(Field {
+                    shape,
+                    modulation: field.modulation,
+                })
+            })
+            .collect::<Result<_, ImageError>>()?;
+        Ok(Fields { fields })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The sum of the forces of the fields on a spore at `position`, at `time`.
+    pub fn force(&self, config: &SimulationConfig, position: Vector, time: f64) -> Vector {
+        self.fields
+            .iter()
+            .map(|field| {
+                let factor = field.modulation.factor(time);
+                if factor == 0.0 {
+                    ZERO_VECTOR
+                } else {
+                    field.shape.force(config, position) * factor
+                }
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::{SimulationConfig, UpdateMode},
+    force_law::ForceLawKind,
+};
+#[cfg(feature = "simd")]
+use crate::{spore::SporeConfigs, vector::Vector};
+#[cfg(feature = "simd")]
+use wide::{f32x8, CmpGe, CmpGt, CmpLe, CmpLt};
+
+/// How the forces of the spores in a bucket on a spore are summed.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForceKernel {
+    /// One spore at a time, works for every force law.
+    Scalar,
+    /// Eight spores at a time. Needs the `simd` feature, the triangle force law and the
+    /// synchronous update mode, and falls back to the scalar kernel otherwise.
+    /// Sums in another order, so the forces differ from the scalar ones in the last bits.
+    Simd,
+}
+
+impl ForceKernel {
+    /// Whether the forces are calculated with the simd kernel in this configuration.
+    pub fn uses_simd(self, config: &SimulationConfig) -> bool {
+        cfg!(feature = "simd")
+            && self == ForceKernel::Simd
+            && config.force_law == ForceLawKind::Triangle
+            // the sequential update mode moves the spores between the buckets,
+            // so the x and y arrays would have to be copied again for every bucket
+            && config.update_mode == UpdateMode::Synchronous
+    }
+}
+
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+/// The triangle force of the spores at `xs`, `ys` on `spore`: the simd version of
+/// `calc_force_from_bucket`, with the positions split in x and y arrays.
+#[cfg(feature = "simd")]
+pub fn calc_force_from_bucket_simd(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spore: Vector,
+    spore_type: u8,
+    xs: &[f32],
+    ys: &[f32],
+    spore_types: &[u8],
+) -> Vector {
+    let own_type = spore_type as usize;
+    let force_reaches = spore_configs.force_reaches.row(own_type);
+    let repulsion_dists = spore_configs.repulsion_dists.row(own_type);
+    let force_factors = spore_configs.force_factors.row(own_type);
+    let (spore_x, spore_y) = (f32x8::splat(spore.x), f32x8::splat(spore.y));
+    let repulsion_amplitude = f32x8::splat(config.repulsion_amplitude);
+
+    let mut force_x = f32x8::ZERO;
+    let mut force_y = f32x8::ZERO;
+    for start in (0..xs.len()).step_by(LANES) {
+        let nr_of_lanes = usize::min(LANES, xs.len() - start);
+        // the lanes past the end are the spore itself, which is skipped like its own position
+        let (mut other_x, mut other_y) = ([spore.x; LANES], [spore.y; LANES]);
+        let (mut force_reach, mut repulsion_dist, mut force_factor) =
+            ([1.0; LANES], [1.0; LANES], [0.0; LANES]);
+        for lane in 0..nr_of_lanes {
+            let other_type = spore_types[start + lane] as usize;
+            other_x[lane] = xs[start + lane];
+            other_y[lane] = ys[start + lane];
+            force_reach[lane] = force_reaches[other_type];
+            repulsion_dist[lane] = repulsion_dists[other_type];
+            force_factor[lane] = force_factors[other_type];
+        }
+        let (force_reach, repulsion_dist, force_factor) = (
+            f32x8::from(force_reach),
+            f32x8::from(repulsion_dist),
+            f32x8::from(force_factor),
+        );
+
+        let (mut x, mut y) = (
+            f32x8::from(other_x) - spore_x,
+            f32x8::from(other_y) - spore_y,
+        );
+        if config.boundaries.x.is_periodic() {
+            x = nearest_copy(x, config.universe_width());
+        }
+        if config.boundaries.y.is_periodic() {
+            y = nearest_copy(y, config.universe_height());
+        }
+        let dist = (x * x + y * y).sqrt();
+        let in_reach = dist.cmp_le(force_reach) & dist.cmp_ge(f32x8::splat(0.000001));
+
+        // the triangle force law
+        let from_repulsion_dist = dist - repulsion_dist;
+        let repulsion = dist * from_repulsion_dist * from_repulsion_dist * repulsion_amplitude
+            / (repulsion_dist * repulsion_dist);
+        let net_force_reach = force_reach - repulsion_dist;
+        let attraction =
+            force_factor * ((net_force_reach - from_repulsion_dist).abs() / net_force_reach / 2.0);
+        let force = dist.cmp_lt(repulsion_dist).blend(repulsion, attraction);
+
+        let scale = in_reach.blend(force / dist, f32x8::ZERO);
+        force_x += x * scale;
+        force_y += y * scale;
+    }
+
+    Vector {
+        x: force_x.reduce_add(),
+        y: force_y.reduce_add(),
+    }
+}
+
+// the distance to the nearest copy across the wrap-around, like `to_calibrated_dist`
+#[cfg(feature = "simd")]
+fn nearest_copy(dist: f32x8, universe_size: f32) -> f32x8 {
+    let across = dist.abs().cmp_gt(f32x8::splat(universe_size / 2.0));
+    let copy_offset = dist
+        .cmp_gt(f32x8::ZERO)
+        .blend(f32x8::splat(universe_size), f32x8::splat(-universe_size));
+    across.blend(dist - copy_offset, dist)
+}
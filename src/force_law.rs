@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, f32::consts::PI};
+
+/// The interaction between a spore and another spore, taken from the interaction matrix.
+#[derive(Debug, Copy, Clone)]
+pub struct PairParams {
+    pub repulsion_dist: f32,
+    pub force_factor: f32,
+    pub force_reach: f32,
+    pub repulsion_amplitude: f32,
+}
+
+/// The shape of the force in terms of distance.
+pub trait ForceLaw {
+    /// The force along the direction of the other spore at `dist` (> 0 and <= the force reach):
+    /// positive attracts, negative repulses.
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32;
+}
+
+/// The force laws that can be selected in the configuration, see the structs for their shape.
+// Written as `{ kind = "morse", width = 0.2 }`. Not an internally tagged enum,
+// because bincode (snapshots) can't read those.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "ForceLawTable", try_from = "ForceLawTable")]
+pub enum ForceLawKind {
+    Triangle,
+    LennardJones,
+    Morse { width: f32 },
+    CosineBump,
+    InversePower { exponent: f32 },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ForceLawTable {
+    kind: ForceLawName,
+    width: Option<f32>,
+    exponent: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ForceLawName {
+    Triangle,
+    LennardJones,
+    Morse,
+    CosineBump,
+    InversePower,
+}
+
+impl From<ForceLawKind> for ForceLawTable {
+    fn from(force_law: ForceLawKind) -> Self {
+        let (kind, width, exponent) = match force_law {
+            ForceLawKind::Triangle => (ForceLawName::Triangle, None, None),
+            ForceLawKind::LennardJones => (ForceLawName::LennardJones, None, None),
+            ForceLawKind::Morse { width } => (ForceLawName::Morse, Some(width), None),
+            ForceLawKind::CosineBump => (ForceLawName::CosineBump, None, None),
+            ForceLawKind::InversePower { exponent } => {
+                (ForceLawName::InversePower, None, Some(exponent))
+            }
+        };
+        ForceLawTable {
+            kind,
+            width,
+            exponent,
+        }
+    }
+}
+
+impl TryFrom<ForceLawTable> for ForceLawKind {
+    type Error = String;
+
+    fn try_from(table: ForceLawTable) -> Result<Self, Self::Error> {
+        match (table.kind, table.width, table.exponent) {
+            (ForceLawName::Triangle, None, None) => Ok(ForceLawKind::Triangle),
+            (ForceLawName::LennardJones, None, None) => Ok(ForceLawKind::LennardJones),
+            (ForceLawName::Morse, Some(width), None) => Ok(ForceLawKind::Morse { width }),
+            (ForceLawName::CosineBump, None, None) => Ok(ForceLawKind::CosineBump),
+            (ForceLawName::InversePower, None, Some(exponent)) => {
+                Ok(ForceLawKind::InversePower { exponent })
+            }
+            (ForceLawName::Morse, ..) => Err("the morse force law needs a width only".to_string()),
+            (ForceLawName::InversePower, ..) => {
+                Err("the inverse_power force law needs an exponent only".to_string())
+            }
+            _ => Err("only morse has a width and only inverse_power has an exponent".to_string()),
+        }
+    }
+}
+
+impl ForceLaw for ForceLawKind {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        match *self {
+            ForceLawKind::Triangle => Triangle.evaluate(dist, params),
+            ForceLawKind::LennardJones => LennardJones.evaluate(dist, params),
+            ForceLawKind::Morse { width } => Morse { width }.evaluate(dist, params),
+            ForceLawKind::CosineBump => CosineBump.evaluate(dist, params),
+            ForceLawKind::InversePower { exponent } => {
+                InversePower { exponent }.evaluate(dist, params)
+            }
+        }
+    }
+}
+
+/// The original force: quadratic repulsion up to the repulsion distance, then the force factor
+/// halved, linearly decreasing to zero at the force reach.
+pub struct Triangle;
+
+impl ForceLaw for Triangle {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        if dist < params.repulsion_dist {
+            dist * repulsion(dist, params)
+        } else {
+            let not_repulsion_dist = dist - params.repulsion_dist;
+            let net_force_reach = params.force_reach - params.repulsion_dist;
+            let dist_from_force_reach_center = (net_force_reach - not_repulsion_dist).abs();
+            let scale = dist_from_force_reach_center / net_force_reach / 2.0;
+            params.force_factor * scale
+        }
+    }
+}
+
+/// 12-6 Lennard-Jones, with its minimum at the repulsion distance and the absolute force factor
+/// as depth. A negative force factor leaves only the repulsive part.
+pub struct LennardJones;
+
+impl ForceLaw for LennardJones {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        let sigma = params.repulsion_dist / 2.0_f32.powf(1.0 / 6.0);
+        let epsilon = params.force_factor.abs();
+        let sigma_6 = (sigma / dist).powi(6);
+        let force = 24.0 * epsilon / dist
+            * (params.force_factor.signum() * sigma_6 - 2.0 * sigma_6 * sigma_6);
+        clamp_to_max_force(force, params)
+    }
+}
+
+/// Morse potential with its minimum at the repulsion distance, the absolute force factor as depth
+/// and `width` as the inverse width of the well. A negative force factor leaves only repulsion.
+pub struct Morse {
+    pub width: f32,
+}
+
+impl ForceLaw for Morse {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        let decay = (-self.width * (dist - params.repulsion_dist)).exp();
+        let force = 2.0
+            * self.width
+            * params.force_factor.abs()
+            * (params.force_factor.signum() * decay - decay * decay);
+        clamp_to_max_force(force, params)
+    }
+}
+
+/// Like the triangle, but the force is a smooth cosine bump between the repulsion distance and
+/// the force reach, peaking at the force factor halved.
+pub struct CosineBump;
+
+impl ForceLaw for CosineBump {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        if dist < params.repulsion_dist {
+            dist * repulsion(dist, params)
+        } else {
+            let net_force_reach = params.force_reach - params.repulsion_dist;
+            let phase = 2.0 * PI * (dist - params.repulsion_dist) / net_force_reach;
+            params.force_factor * (1.0 - phase.cos()) / 4.0
+        }
+    }
+}
+
+/// Like the triangle, but beyond the repulsion distance the force factor halved decays with
+/// (repulsion distance / distance) ^ `exponent`.
+pub struct InversePower {
+    pub exponent: f32,
+}
+
+impl ForceLaw for InversePower {
+    #[inline]
+    fn evaluate(&self, dist: f32, params: &PairParams) -> f32 {
+        if dist < params.repulsion_dist {
+            dist * repulsion(dist, params)
+        } else {
+            params.force_factor / 2.0 * (params.repulsion_dist / dist).powf(self.exponent)
+        }
+    }
+}
+
+// per unit of distance, zero at the repulsion distance
+#[inline]
+fn repulsion(dist: f32, params: &PairParams) -> f32 {
+    (dist - params.repulsion_dist).powi(2) * params.repulsion_amplitude
+        / params.repulsion_dist.powi(2)
+}
+
+// the laws that diverge at short distances would make close spores explode
+#[inline]
+fn clamp_to_max_force(force: f32, params: &PairParams) -> f32 {
+    let max_force = (params.repulsion_amplitude * params.repulsion_dist).abs();
+    force.clamp(-max_force, max_force)
+}
@@ -1,79 +1,168 @@
 use crate::{
-    bucket::get_bucket,
-    configuration::{
-        MAX_FORCE_AMPLITUDE, MAX_FORCE_REACH, MAX_REPULSION_DIST, NR_BUCKETS, NR_HORZ_BUCKETS,
-        NR_VERT_BUCKETS, NUMBER_OF_CONFIGS, UNIVERSE_HEIGHT, UNIVERSE_WIDTH,
-        USE_PREVIOUS_CONFIGURATIONS,
-    },
+    bucket::Grid,
+    configuration::{Interactions, SimulationConfig},
+    field::Fields,
+    obstacle::Obstacles,
     spore::{SporeConfigs, SporesState},
     vector::{Vector, ZERO_VECTOR},
 };
 use rand::prelude::*;
+use std::sync::Arc;
 
-pub fn generate_spore_configs() -> SporeConfigs {
-    if USE_PREVIOUS_CONFIGURATIONS {
-        return PREVIOUS_CONFIGS;
+/// The spore configurations of the configuration, or random ones. The masses and radii of the
+/// configuration replace those of the spore configurations, see `SporeConfigs::apply_radii`.
+pub fn generate_spore_configs(config: &SimulationConfig, rng: &mut impl Rng) -> SporeConfigs {
+    let mut spore_configs = generate_interactions(config, rng);
+    let number_of_configs = spore_configs.number_of_configs();
+    // missing from a pasted table without them
+    if spore_configs.masses.is_empty() {
+        spore_configs.masses = vec![1.0; number_of_configs];
     }
+    if spore_configs.radii.is_empty() {
+        spore_configs.radii = vec![0.0; number_of_configs];
+    }
+    if !config.masses.is_empty() {
+        spore_configs.masses = config.masses.clone();
+    }
+    if !config.radii.is_empty() {
+        spore_configs.radii = config.radii.clone();
+    }
+    spore_configs.apply_radii();
+    spore_configs
+}
 
-    let mut rng = rand::rng();
+fn generate_interactions(config: &SimulationConfig, rng: &mut impl Rng) -> SporeConfigs {
+    if let Some(spore_configs) = &config.spore_configs {
+        return spore_configs.clone();
+    }
+    if config.use_previous_configurations {
+        return previous_configs();
+    }
 
-    let mut repulsion_dists = [0.0; NUMBER_OF_CONFIGS];
-    let mut force_factors = [0.0; NUMBER_OF_CONFIGS];
-    let mut force_reaches = [0.0; NUMBER_OF_CONFIGS];
+    let number_of_configs = config.number_of_configs;
+    match config.interactions {
+        Interactions::Pairwise => {
+            let mut spore_configs = SporeConfigs::new(number_of_configs);
+            for own_type in 0..number_of_configs {
+                for other_type in 0..number_of_configs {
+                    let index = (own_type, other_type);
+                    (
+                        spore_configs.repulsion_dists[index],
+                        spore_configs.force_factors[index],
+                        spore_configs.force_reaches[index],
+                    ) = generate_interaction(config, rng);
+                }
+            }
+            spore_configs
+        }
+        Interactions::Symmetric => {
+            let mut spore_configs = SporeConfigs::new(number_of_configs);
+            for own_type in 0..number_of_configs {
+                for other_type in own_type..number_of_configs {
+                    let interaction = generate_interaction(config, rng);
+                    for index in [(own_type, other_type), (other_type, own_type)] {
+                        (
+                            spore_configs.repulsion_dists[index],
+                            spore_configs.force_factors[index],
+                            spore_configs.force_reaches[index],
+                        ) = interaction;
+                    }
+                }
+            }
+            spore_configs
+        }
+        Interactions::ByOtherType => {
+            let mut repulsion_dists = vec![0.0; number_of_configs];
+            let mut force_factors = vec![0.0; number_of_configs];
+            let mut force_reaches = vec![0.0; number_of_configs];
 
-    (0..NUMBER_OF_CONFIGS).for_each(|index| {
-        repulsion_dists[index] = rng.random_range(0.08..=1.2) * MAX_REPULSION_DIST;
-        force_factors[index] = rng.random_range(0.15..=1.0)
-            * if rng.random_bool(0.65) { 1.0 } else { -1.0 }
-            * MAX_FORCE_AMPLITUDE;
-        force_reaches[index] = rng.random_range(0.20..=1.0) * MAX_FORCE_REACH;
-    });
+            (0..number_of_configs).for_each(|index| {
+                (
+                    repulsion_dists[index],
+                    force_factors[index],
+                    force_reaches[index],
+                ) = generate_interaction(config, rng);
+            });
 
-    SporeConfigs {
-        repulsion_dists,
-        force_factors,
-        force_reaches,
+            SporeConfigs::by_other_type(&repulsion_dists, &force_factors, &force_reaches)
+        }
     }
 }
 
-pub fn generate_spores(nr_of_spores: u16) -> SporesState {
-    let exp_nr_spores_per_bucket: usize = nr_of_spores as usize / NR_BUCKETS;
+// the random force reaches are at least this part of max_force_reach
+const MIN_FORCE_REACH: f32 = 0.20;
+
+// (repulsion dist, force factor, force reach)
+fn generate_interaction(config: &SimulationConfig, rng: &mut impl Rng) -> (f32, f32, f32) {
+    let repulsion_dist = rng.random_range(0.08..=1.2) * config.max_repulsion_dist;
+    let force_factor = rng.random_range(0.15..=1.0)
+        * if rng.random_bool(0.65) { 1.0 } else { -1.0 }
+        * config.max_force_amplitude;
+    let force_reach = rng.random_range(MIN_FORCE_REACH..=1.0) * config.max_force_reach;
+    (repulsion_dist, force_factor, force_reach)
+}
 
-    let mut rng = rand::rng();
+// a universe that is nearly all obstacle shouldn't hang the generation
+const MAX_PLACEMENT_TRIES: usize = 1000;
 
-    // TODO: change to flat vec because inefficient!
-    // see https://old.reddit.com/r/rust/comments/3bu7a8/access_time_of_vec_vs_array/cspq1bo/
-    // see also: https://old.reddit.com/r/rust/comments/3bu7a8/access_time_of_vec_vs_array/csv37zl/
-    // which say to use probably Matrix from NAlgebra, although my matrices are 3D, not 2D, with the lowest level dynamic sized
-    let mut positions =
-        vec![vec![Vec::with_capacity(exp_nr_spores_per_bucket); NR_HORZ_BUCKETS]; NR_VERT_BUCKETS];
-    let mut speeds =
-        vec![vec![Vec::with_capacity(exp_nr_spores_per_bucket); NR_HORZ_BUCKETS]; NR_VERT_BUCKETS];
-    let mut spore_types =
-        vec![vec![Vec::with_capacity(exp_nr_spores_per_bucket); NR_HORZ_BUCKETS]; NR_VERT_BUCKETS];
+/// Places the spores randomly, outside of the obstacles. A spore that finds no free place in
+/// `MAX_PLACEMENT_TRIES` tries is left out, so a universe that is nearly all obstacle gets fewer spores.
+pub fn generate_spores(
+    config: &SimulationConfig,
+    grid: Grid,
+    obstacles: Arc<Obstacles>,
+    fields: Arc<Fields>,
+    nr_of_spores: u32,
+    rng: &mut impl Rng,
+) -> SporesState {
+    let nr_of_spores = nr_of_spores as usize;
+    let mut positions = Vec::with_capacity(nr_of_spores);
+    let mut spore_types = Vec::with_capacity(nr_of_spores);
 
     for _ in 0..nr_of_spores {
-        let x: f32 = rng.random_range(0.0..UNIVERSE_WIDTH);
-        let y: f32 = rng.random_range(0.0..UNIVERSE_HEIGHT);
-
-        let (horz, vert) = get_bucket(x, y);
+        let mut random_position = || Vector {
+            x: rng.random_range(0.0..config.universe_width()),
+            y: rng.random_range(0.0..config.universe_height()),
+        };
+        let Some(position) = (0..MAX_PLACEMENT_TRIES)
+            .map(|_| random_position())
+            .find(|position| !obstacles.contains(&grid, *position))
+        else {
+            continue;
+        };
 
-        // println!("{}, {}", horz, vert);
-        positions[vert][horz].push(Vector { x, y });
-        speeds[vert][horz].push(ZERO_VECTOR);
-        spore_types[vert][horz].push(rng.random_range(0..NUMBER_OF_CONFIGS as u8));
+        positions.push(position);
+        spore_types.push(rng.random_range(0..config.number_of_configs) as u8);
     }
-    SporesState {
-        positions,
-        speeds,
-        spore_types,
+    let speeds = vec![ZERO_VECTOR; positions.len()];
+    SporesState::from_spores(grid, obstacles, fields, positions, speeds, spore_types)
+}
+
+/// The shortest force reach the spore configurations of the configuration can have.
+pub fn shortest_force_reach(config: &SimulationConfig) -> f32 {
+    let shortest = |spore_configs: &SporeConfigs| {
+        spore_configs
+            .force_reaches
+            .rows()
+            .flatten()
+            .fold(f32::INFINITY, |min, reach| min.min(*reach))
+    };
+    match &config.spore_configs {
+        Some(spore_configs) => shortest(spore_configs),
+        None if config.use_previous_configurations => shortest(&previous_configs()),
+        None => MIN_FORCE_REACH * config.max_force_reach,
     }
 }
 
-pub const PREVIOUS_CONFIGS: SporeConfigs = SporeConfigs {
-    repulsion_dists: [9.82, 5.97, 9.16, 17.59, 17.57, 5.08, 8.20, 10.20, 12.20],
-    force_factors: [0.08, -0.04, 0.06, 0.07, 0.10, -0.08, 0.02, 0.04, -0.02],
-    force_reaches: [
-        27.41, 58.38, 38.75, 36.55, 52.39, 65.82, 30.05, 40.34, 45.98,
-    ],
-};
+pub const PREVIOUS_NUMBER_OF_CONFIGS: usize = 9;
+
+// the force only depends on the other spore's type
+pub fn previous_configs() -> SporeConfigs {
+    SporeConfigs::by_other_type(
+        &[9.82, 5.97, 9.16, 17.59, 17.57, 5.08, 8.20, 10.20, 12.20],
+        &[0.08, -0.04, 0.06, 0.07, 0.10, -0.08, 0.02, 0.04, -0.02],
+        &[
+            27.41, 58.38, 38.75, 36.55, 52.39, 65.82, 30.05, 40.34, 45.98,
+        ],
+    )
+}
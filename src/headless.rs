@@ -0,0 +1,116 @@
+use crate::{snapshot::save_snapshot, trajectory::TrajectoryRecorder, world::World};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When to stop a headless run: whichever limit is reached first.
+pub struct RunLimits {
+    pub max_ticks: Option<u32>,
+    pub max_duration: Option<Duration>,
+}
+
+impl RunLimits {
+    fn reached(&self, tick: u32, elapsed: Duration) -> bool {
+        self.max_ticks.is_some_and(|max_ticks| tick >= max_ticks)
+            || self
+                .max_duration
+                .is_some_and(|max_duration| elapsed >= max_duration)
+    }
+}
+
+/// Runs the simulation without a window, printing the progress to stdout.
+/// The limits count from the start of this run, also when resuming from a snapshot.
+pub fn run_headless(
+    mut world: World,
+    limits: &RunLimits,
+    snapshot_path: Option<&Path>,
+    mut recorder: Option<TrajectoryRecorder>,
+) {
+    println!(
+        "\nPycniospores, headless run with {} spores from tick {}.\n\nSeed: {}\n\nSpore configurations, to paste at the end of the config file:\n\n{:.2}\n",
+        world.nr_of_spores(),
+        world.tick(),
+        world.seed().unwrap_or_default(),
+        world.spore_configs()
+    );
+
+    let start = Instant::now();
+    let mut last_progress = start;
+    let mut tick: u32 = 0;
+    // the reactions per rule since the last progress
+    let mut reactions = vec![0; world.config().reactions.len()];
+
+    while !limits.reached(tick, start.elapsed()) {
+        world.step();
+        tick += 1;
+        for (total, count) in reactions.iter_mut().zip(world.reaction_counts()) {
+            *total += count;
+        }
+
+        if let Some(trajectory) = recorder.as_mut() {
+            if let Err(err) = trajectory.record(&world) {
+                eprintln!("Stopped recording: {}", err);
+                recorder = None;
+            }
+        }
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            print_progress(tick, start.elapsed(), limits, world.kinetic_temperature());
+            if !reactions.is_empty() {
+                println!("        Reactions per rule: {:?}", reactions);
+                reactions.iter_mut().for_each(|total| *total = 0);
+            }
+        }
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "Finished after {} ticks in {:.2}s: AVG ticks/s: {:.2}",
+        tick,
+        elapsed.as_secs_f32(),
+        ticks_per_sec(tick, elapsed)
+    );
+
+    if let Some(Err(err)) = recorder.map(TrajectoryRecorder::finish) {
+        eprintln!("Could not finish the recording: {}", err);
+    }
+
+    if let Some(path) = snapshot_path {
+        match save_snapshot(&world, path) {
+            Ok(()) => println!("Saved tick {} to {}", world.tick(), path.display()),
+            Err(err) => eprintln!("Could not save {}: {}", path.display(), err),
+        }
+    }
+}
+
+fn print_progress(tick: u32, elapsed: Duration, limits: &RunLimits, temperature: f32) {
+    let tick_progress = limits
+        .max_ticks
+        .map(|max_ticks| tick as f32 / max_ticks as f32);
+    let time_progress = limits
+        .max_duration
+        .map(|max_duration| elapsed.as_secs_f32() / max_duration.as_secs_f32());
+    let progress = match (tick_progress, time_progress) {
+        (Some(a), Some(b)) => f32::max(a, b),
+        (Some(a), None) | (None, Some(a)) => a,
+        (None, None) => 0.0,
+    };
+
+    println!(
+        "{:5.1}% - Time: {}m{}s - Tick: {} - AVG ticks/s: {:.2} - Temperature: {:.4}",
+        progress * 100.0,
+        elapsed.as_secs() / 60,
+        elapsed.as_secs() % 60,
+        tick,
+        ticks_per_sec(tick, elapsed),
+        temperature
+    );
+}
+
+fn ticks_per_sec(tick: u32, elapsed: Duration) -> f32 {
+    tick as f32 / elapsed.as_secs_f32()
+}
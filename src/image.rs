@@ -0,0 +1,68 @@
+use std::{fmt, fs::File, io, path::Path};
+
+/// The brightness and opacity of the pixels of a PNG, row by row from the top left.
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    // (luma, alpha) per pixel
+    pub pixels: Vec<(u8, u8)>,
+}
+
+impl GrayImage {
+    pub fn load(path: &Path) -> Result<GrayImage, ImageError> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        // palettes and low bit depths become 8 bit gray(alpha) or rgb(a)
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let channels = info.color_type.samples();
+
+        let luma = |r: u8, g: u8, b: u8| ((r as u32 + g as u32 + b as u32) / 3) as u8;
+        let pixels = buffer[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| match *pixel {
+                [gray] => (gray, 255),
+                [gray, alpha] => (gray, alpha),
+                [r, g, b] => (luma(r, g, b), 255),
+                [r, g, b, alpha, ..] => (luma(r, g, b), alpha),
+                [] => (255, 0),
+            })
+            .collect();
+
+        Ok(GrayImage {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    Decoding(png::DecodingError),
+}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> Self {
+        ImageError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for ImageError {
+    fn from(err: png::DecodingError) -> Self {
+        ImageError::Decoding(err)
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(err) => write!(f, "cannot read image file: {}", err),
+            ImageError::Decoding(err) => write!(f, "cannot decode image file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
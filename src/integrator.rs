@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bond::Bond,
+    boundary::apply_boundaries,
+    configuration::SimulationConfig,
+    movement_calculator::calc_forces,
+    spore::{SporeConfigs, SporesState},
+    thermostat::{damping_rate, thermal_noise, Thermostat},
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// How a tick advances the speeds and positions of the spores from their forces.
+///
+/// A tick covers a time of `dt`. The friction is the damping over a time of 1.0,
+/// so the integrators agree on how fast the spores slow down whatever the timestep.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Moves with the old speed, then updates the speed.
+    ExplicitEuler,
+    /// Updates the speed, then moves with the new speed: the original update at `dt = 1.0`
+    /// in the sequential update mode.
+    SemiImplicitEuler,
+    /// Second order and symplectic without friction, two force evaluations per tick.
+    VelocityVerlet,
+    /// Classic fourth order Runge-Kutta, four force evaluations per tick.
+    Rk4,
+}
+
+impl Integrator {
+    /// The Euler integrators only need the forces at the start of the tick, so they can also
+    /// update the spores bucket by bucket. The others evaluate the forces at intermediate positions.
+    pub fn evaluates_forces_once(self) -> bool {
+        matches!(
+            self,
+            Integrator::ExplicitEuler | Integrator::SemiImplicitEuler
+        )
+    }
+
+    /// The new (unwrapped) position and speed of a single spore, from its speed damped over the
+    /// tick (see `thermostat::damp`) and its acceleration: the force divided by its mass.
+    pub(crate) fn euler_step(
+        self,
+        config: &SimulationConfig,
+        position: Vector,
+        speed: Vector,
+        damped_speed: Vector,
+        acceleration: Vector,
+    ) -> (Vector, Vector) {
+        let dt = config.dt;
+        match self {
+            Integrator::ExplicitEuler => (position + speed * dt, damped_speed + acceleration * dt),
+            Integrator::SemiImplicitEuler => {
+                let new_speed = damped_speed + acceleration * dt;
+                (position + new_speed * dt, new_speed)
+            }
+            Integrator::VelocityVerlet | Integrator::Rk4 => {
+                unreachable!("{:?} integrates all spores at once", self)
+            }
+        }
+    }
+
+    /// Advances all spores at once. The spores are not sorted into their new buckets yet.
+    pub(crate) fn step_whole_state(
+        self,
+        config: &SimulationConfig,
+        spore_configs: &SporeConfigs,
+        spores: &mut SporesState,
+    ) {
+        let dt = config.dt;
+        // dv/dt = force - gamma * v, which damps the speed by `friction` over a time of 1.0
+        let gamma = damping_rate(config);
+        let (positions, speeds) = (&spores.positions, &spores.speeds);
+        // the forces divided by the masses at the positions, `elapsed` after the start of the tick
+        let accelerations_at = |positions: &[Vector], elapsed: f32| {
+            accelerations_at(config, spore_configs, spores, positions, elapsed)
+        };
+
+        let (new_positions, new_speeds): (Vec<Vector>, Vec<Vector>) = match self {
+            Integrator::VelocityVerlet => {
+                // half kick, drift, half kick with the friction of the last half taken implicitly
+                let accelerations = accelerations_at(positions, 0.0);
+                let half_speeds: Vec<Vector> = (0..speeds.len())
+                    .map(|i| speeds[i] + (accelerations[i] - speeds[i] * gamma) * (dt / 2.0))
+                    .collect();
+                let new_positions: Vec<Vector> = (0..positions.len())
+                    .map(|i| positions[i] + half_speeds[i] * dt)
+                    .collect();
+                let new_accelerations = accelerations_at(&new_positions, dt);
+                let new_speeds = (0..speeds.len())
+                    .map(|i| {
+                        (half_speeds[i] + new_accelerations[i] * (dt / 2.0))
+                            * (1.0 / (1.0 + gamma * dt / 2.0))
+                    })
+                    .collect();
+                (new_positions, new_speeds)
+            }
+            Integrator::Rk4 => {
+                // the derivative of (position, speed), evaluated at an offset of the current state,
+                // `factor` after the start of the tick
+                let derivative = |offset: &[(Vector, Vector)], factor: f32| {
+                    let offset_positions: Vec<Vector> = (0..positions.len())
+                        .map(|i| positions[i] + offset[i].0 * factor)
+                        .collect();
+                    let accelerations = accelerations_at(&offset_positions, factor);
+                    (0..speeds.len())
+                        .map(|i| {
+                            let speed = speeds[i] + offset[i].1 * factor;
+                            (speed, accelerations[i] - speed * gamma)
+                        })
+                        .collect::<Vec<(Vector, Vector)>>()
+                };
+                let k1 = derivative(&vec![(ZERO_VECTOR, ZERO_VECTOR); speeds.len()], 0.0);
+                let k2 = derivative(&k1, dt / 2.0);
+                let k3 = derivative(&k2, dt / 2.0);
+                let k4 = derivative(&k3, dt);
+                (0..speeds.len())
+                    .map(|i| {
+                        let weighted = |k: fn(&(Vector, Vector)) -> Vector| {
+                            (k(&k1[i]) + k(&k2[i]) * 2.0 + k(&k3[i]) * 2.0 + k(&k4[i])) * (dt / 6.0)
+                        };
+                        (
+                            positions[i] + weighted(|k| k.0),
+                            speeds[i] + weighted(|k| k.1),
+                        )
+                    })
+                    .unzip()
+            }
+            Integrator::ExplicitEuler | Integrator::SemiImplicitEuler => {
+                unreachable!("{:?} only needs the forces at the start of the tick", self)
+            }
+        };
+
+        // the thermal noise on top of the damping in the derivatives
+        let new_speeds: Vec<Vector> = if config.thermostat == Thermostat::Off {
+            new_speeds
+        } else {
+            new_speeds
+                .into_iter()
+                .zip(spores.ids.iter().zip(&spores.spore_types))
+                .map(|(speed, (id, spore_type))| {
+                    let mass = spore_configs.masses[*spore_type as usize];
+                    speed + thermal_noise(config, *id, spores.time, mass)
+                })
+                .collect()
+        };
+        (spores.positions, spores.speeds) = spores
+            .positions
+            .iter()
+            .zip(new_positions)
+            .zip(new_speeds)
+            .map(|((old_position, position), speed)| {
+                let (position, speed) =
+                    spores
+                        .obstacles
+                        .collide(&spores.grid, *old_position, position, speed);
+                apply_boundaries(config, position, speed)
+            })
+            .unzip();
+    }
+}
+
+// the intermediate positions can have left their bucket: sort them into buckets again
+// and hand the forces divided by the masses back in the order of the positions
+fn accelerations_at(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &SporesState,
+    positions: &[Vector],
+    elapsed: f32,
+) -> Vec<Vector> {
+    // numbered in the order of the positions, so the ids are the old indexes, also in the bonds
+    let mut sorted = SporesState::from_spores(
+        spores.grid.clone(),
+        spores.obstacles.clone(),
+        spores.fields.clone(),
+        positions
+            .iter()
+            .map(|position| apply_boundaries(config, *position, ZERO_VECTOR).0)
+            .collect(),
+        vec![ZERO_VECTOR; positions.len()],
+        spores.spore_types.clone(),
+    );
+    sorted.time = spores.time + elapsed as f64;
+    let indexes = spores.indexes_by_id();
+    sorted.bonds = spores
+        .bonds
+        .iter()
+        .map(|bond| Bond {
+            ids: bond.ids.map(|id| indexes[id as usize] as u32),
+            ..*bond
+        })
+        .collect();
+    let sorted_forces = calc_forces(config, spore_configs, &sorted, 0..sorted.len());
+
+    let mut accelerations = vec![ZERO_VECTOR; positions.len()];
+    for ((old_index, spore_type), force) in sorted
+        .ids
+        .iter()
+        .zip(&sorted.spore_types)
+        .zip(sorted_forces)
+    {
+        accelerations[*old_index as usize] =
+            force * (1.0 / spore_configs.masses[*spore_type as usize]);
+    }
+    accelerations
+}
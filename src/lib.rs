@@ -0,0 +1,30 @@
+//! The pycniospores simulation core: spores attracting and repulsing each other in a universe
+//! that wraps around or has walls.
+//!
+//! Start from a [`World`], which holds the state of a run and advances it tick by tick.
+
+pub mod bond;
+pub mod boundary;
+pub mod bucket;
+pub mod cli;
+pub mod configuration;
+pub mod field;
+pub mod force_kernel;
+pub mod force_law;
+pub mod generators;
+pub mod headless;
+pub mod image;
+pub mod integrator;
+pub mod movement_calculator;
+pub mod obstacle;
+pub mod reaction;
+pub mod snapshot;
+pub mod spore;
+pub mod spore_mover;
+pub mod thermostat;
+pub mod trajectory;
+pub mod vector;
+pub mod world;
+
+pub use configuration::SimulationConfig;
+pub use world::World;
@@ -1,54 +1,54 @@
-use clap::{App, Arg};
-use configuration::{
-    NUMBER_OF_SPORES, UNIVERSE_HEIGHT, UNIVERSE_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH,
-};
+use clap::{App, Arg, ArgMatches};
 use ggez::{
     self,
     conf::{self},
-    event, GameResult,
+    event::{self, EventLoop},
+    Context, GameError, GameResult,
 };
+use pycniospores::{cli, headless::run_headless, trajectory::TrajectoryReader};
+use replay::Replay;
 use simulation::Simulation;
 use std::{
     env,
-    path::{self, PathBuf},
-    str::FromStr,
+    path::{self, Path, PathBuf},
 };
 
-mod bucket;
-mod configuration;
-mod generators;
-mod movement_calculator;
+mod replay;
 mod simulation;
-mod spore;
-mod spore_mover;
-mod vector;
 
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
-const HEIGHT_RATIO: f32 = WINDOW_HEIGHT / UNIVERSE_HEIGHT;
-const WIDTH_RATIO: f32 = WINDOW_WIDTH / UNIVERSE_WIDTH;
-
 const MAX_ZOOM: f32 = 4.0;
-const MIN_ZOOM: f32 = HEIGHT_RATIO; // 1.0 Ideally should be const fn f32::min(HEIGHT_RATIO, WIDTH_RATIO)
 const ZOOM_SPEED: f32 = 0.03;
 const MOVE_INCREMENT: f32 = 40.0;
 
+const DEFAULT_SNAPSHOT_PATH: &str = "pycniospores.snapshot";
+
 pub fn main() -> GameResult {
-    let nr_of_spores = get_nr_of_spores();
+    let matches = get_arg_matches();
+    if let Some(path) = matches.value_of("replay") {
+        return replay(&matches, Path::new(path));
+    }
 
-    let cb = ggez::ContextBuilder::new("Pycniospores", "Pycniospores")
-        .add_resource_path(get_resource_dir())
-        .window_mode(
-            conf::WindowMode::default()
-                .dimensions(WINDOW_WIDTH, WINDOW_HEIGHT)
-                .fullscreen_type(conf::FullscreenType::Windowed)
-                .resizable(true),
-        )
-        .window_setup(conf::WindowSetup::default().title("Pycniospores"));
+    let world = cli::get_world(&matches).map_err(GameError::ConfigError)?;
+    let recorder = cli::get_recorder(&matches, &world).map_err(GameError::ConfigError)?;
 
-    let (mut ctx, event_loop) = cb.build()?;
-    let state = Simulation::new(&mut ctx, nr_of_spores)?;
+    if matches.is_present("headless") {
+        let limits = cli::get_run_limits(&matches).map_err(GameError::ConfigError)?;
+        let snapshot_path = matches.value_of("snapshot").map(Path::new);
+        run_headless(world, &limits, snapshot_path, recorder);
+        return Ok(());
+    }
+    let snapshot_path = PathBuf::from(
+        matches
+            .value_of("snapshot")
+            .unwrap_or(DEFAULT_SNAPSHOT_PATH),
+    );
+
+    let (mut ctx, event_loop) =
+        build_context(world.config().window_width, world.config().window_height)?;
+    let state = Simulation::new(&mut ctx, world, snapshot_path, recorder)?;
 
     println!(
         "\nWelcome to Pycniospores! A spores simulator.\n
@@ -56,34 +56,90 @@ pub fn main() -> GameResult {
         .\tto zoom out\n
         arrows\tto move around\n
         space\tto pause\n
+        s\tto save a snapshot\n
+        l\tto load the snapshot\n
+        click\ton a spore to show its id\n
         esc\tto quit\n\n
-        Spore configuration:\n\n {}\n",
-        format!("{:.2?}", state.spore_configs)
+        Seed: {}\n
+        Spore configurations, to paste at the end of the config file:\n\n{:.2}\n",
+        state.world.seed().unwrap_or_default(),
+        state.world.spore_configs()
+    );
+    event::run(ctx, event_loop, state)
+}
+
+fn replay(matches: &ArgMatches, path: &Path) -> GameResult {
+    let config = cli::get_config(matches).map_err(GameError::ConfigError)?;
+    let reader = TrajectoryReader::open(path)
+        .map_err(|err| GameError::ResourceLoadError(format!("{}: {}", path.display(), err)))?;
+
+    let (mut ctx, event_loop) = build_context(config.window_width, config.window_height)?;
+    let state = Replay::new(&mut ctx, reader, config.window_width, config.window_height)?;
+
+    println!(
+        "\nWelcome to Pycniospores! Replaying {}.\n
+        space\tto play or pause\n
+        [ ]\tto step a frame backward or forward\n
+        - =\tto play slower or faster\n
+        pgup pgdn\tto jump backward or forward\n
+        home end\tto go to the first or last frame\n
+        0-9 enter\tto go to a tick\n
+        click\ton the timeline to go to that tick\n
+        click\ton a spore to follow it and show its id\n
+        ,\tto zoom in\n
+        .\tto zoom out\n
+        arrows\tto move around\n
+        esc\tto quit\n",
+        path.display()
     );
     event::run(ctx, event_loop, state)
 }
 
+fn build_context(window_width: f32, window_height: f32) -> GameResult<(Context, EventLoop<()>)> {
+    ggez::ContextBuilder::new("Pycniospores", "Pycniospores")
+        .add_resource_path(get_resource_dir())
+        .window_mode(
+            conf::WindowMode::default()
+                .dimensions(window_width, window_height)
+                .fullscreen_type(conf::FullscreenType::Windowed)
+                .resizable(true),
+        )
+        .window_setup(conf::WindowSetup::default().title("Pycniospores"))
+        .build()
+}
+
 // TODO update to clap v3 when available
-// TODO also read if random configs or not
-fn get_nr_of_spores() -> u16 {
-    let matches = App::new("Pycniospores")
+fn get_arg_matches() -> ArgMatches<'static> {
+    App::new("Pycniospores")
         .version("0.1")
         .author("@wmclt on Gitlab")
         .about("The large particle simulator")
+        .args(&cli::world_args())
         .arg(
-            Arg::with_name("number")
-                .help("Sets the number of particles ('spores')")
-                .short("n")
-                .long("number")
+            Arg::with_name("snapshot")
+                .help("Sets the snapshot file to save to: on key press or at the end of a headless run")
+                .long("snapshot")
                 .takes_value(true),
         )
-        .get_matches();
-    let nr_of_spores = matches
-        .value_of("number")
-        .map(|s| FromStr::from_str(s))
-        .map(|a| a.unwrap())
-        .unwrap_or(NUMBER_OF_SPORES);
-    nr_of_spores
+        .arg(
+            Arg::with_name("replay")
+                .help("Replays a recorded trajectory file instead of simulating")
+                .long("replay")
+                .takes_value(true)
+                .conflicts_with_all(&["headless", "resume", "record", "number", "seed"]),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .help("Runs the simulation without a window, needs --ticks and/or --duration")
+                .long("headless"),
+        )
+        .args(
+            &cli::run_limit_args()
+                .into_iter()
+                .map(|arg| arg.requires("headless"))
+                .collect::<Vec<_>>(),
+        )
+        .get_matches()
 }
 
 fn get_resource_dir() -> PathBuf {
@@ -1,71 +1,278 @@
-use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+    IntoParallelRefMutIterator, ParallelIterator,
+};
+use std::ops::Range;
 
+#[cfg(feature = "simd")]
+use crate::force_kernel::calc_force_from_bucket_simd;
 use crate::{
-    bucket::{get_neighbors, BucketCoord},
-    configuration::{
-        FRICTION, MAX_FORCE_REACH, REPULSION_AMPLITUDE, UNIVERSE_HEIGHT, UNIVERSE_WIDTH,
-    },
+    bond::bond_forces,
+    boundary::{apply_boundaries, wall_force, Boundary},
+    bucket::{BucketCoord, Grid},
+    configuration::{SimulationConfig, UpdateMode},
+    force_law::{ForceLaw, PairParams},
     spore::{SporeConfigs, SporesState},
+    thermostat::damp,
     vector::{Vector, ZERO_VECTOR},
 };
 
-pub fn calc_new_positions_and_speeds(
+// the forces are collected in the order of the spores and every force is summed sequentially,
+// so the result doesn't depend on how rayon splits the work
+pub fn calc_forces(
+    config: &SimulationConfig,
     spore_configs: &SporeConfigs,
-    spores: &mut SporesState,
-    (horz, vert): BucketCoord,
-) -> (Vec<usize>, (Vec<Vector>, Vec<Vector>)) {
-    let forces = calc_forces(spores, (horz, vert), spore_configs);
-    update_spores_with_forces(spores, (horz, vert), forces)
+    spores: &SporesState,
+    range: Range<usize>,
+) -> Vec<Vector> {
+    let bond_forces = bond_forces(config, spores);
+    calc_forces_with_bonds(config, spore_configs, spores, range, &bond_forces)
 }
 
-fn calc_forces(
-    spores: &mut SporesState,
-    (horz, vert): BucketCoord,
+/// Like `calc_forces`, with the `bond_forces` on all spores already calculated: the sequential
+/// update mode calculates them once per tick instead of visiting every bond for every bucket.
+pub fn calc_forces_with_bonds(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &SporesState,
+    range: Range<usize>,
+    bond_forces: &[Vector],
+) -> Vec<Vector> {
+    let mut forces = calc_forces_between_spores(config, spore_configs, spores, range.clone());
+    if config.boundaries.has(Boundary::SoftWall) {
+        forces
+            .par_iter_mut()
+            .zip(spores.positions[range.clone()].par_iter())
+            .for_each(|(force, position)| *force += wall_force(config, *position));
+    }
+    if !spores.fields.is_empty() {
+        forces
+            .par_iter_mut()
+            .zip(spores.positions[range.clone()].par_iter())
+            .for_each(|(force, position)| {
+                *force += spores.fields.force(config, *position, spores.time)
+            });
+    }
+    if !bond_forces.is_empty() {
+        forces
+            .par_iter_mut()
+            .zip(bond_forces[range].par_iter())
+            .for_each(|(force, bond_force)| *force += *bond_force);
+    }
+    forces
+}
+
+fn calc_forces_between_spores(
+    config: &SimulationConfig,
     spore_configs: &SporeConfigs,
+    spores: &SporesState,
+    range: Range<usize>,
 ) -> Vec<Vector> {
-    spores.positions[vert][horz]
+    #[cfg(feature = "simd")]
+    if config.force_kernel.uses_simd(config) {
+        return calc_forces_simd(config, spore_configs, spores, range);
+    }
+    if config.update_mode == UpdateMode::Synchronous
+        && range == (0..spores.len())
+        && spore_configs.is_symmetric()
+    {
+        return calc_pair_forces(config, spore_configs, spores);
+    }
+    spores.positions[range.clone()]
         .par_iter()
-        .map(|spore_position| {
-            calculate_forces_on_spore(&spore_configs, *spore_position, spores, (horz, vert))
+        .zip(spores.spore_types[range].par_iter())
+        .map(|(spore_position, spore_type)| {
+            calculate_forces_on_spore(
+                config,
+                spore_configs,
+                *spore_position,
+                *spore_type,
+                spores,
+                spores.grid.get_bucket_from_pos(*spore_position),
+            )
         })
         .collect()
 }
 
-// TODO (Vec<usize>, (Vec<Vector>, Vec<Vector>)) into NewSporesData
-fn update_spores_with_forces(
-    spores: &mut SporesState,
-    (horz, vert): BucketCoord,
-    forces: Vec<Vector>,
-) -> (Vec<usize>, (Vec<Vector>, Vec<Vector>)) {
-    (0..spores.positions[vert][horz].len())
-        .into_par_iter()
-        .map(|index| {
-            (
-                index,
-                spores.positions[vert][horz][index],
-                spores.speeds[vert][horz][index],
-                forces[index],
-            )
+// like `calc_forces`, with the positions split in x and y arrays once for all spores
+#[cfg(feature = "simd")]
+fn calc_forces_simd(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &SporesState,
+    range: Range<usize>,
+) -> Vec<Vector> {
+    let (xs, ys): (Vec<f32>, Vec<f32>) = spores
+        .positions
+        .iter()
+        .map(|position| (position.x, position.y))
+        .unzip();
+    spores.positions[range.clone()]
+        .par_iter()
+        .zip(spores.spore_types[range].par_iter())
+        .map(|(spore_position, spore_type)| {
+            spores
+                .grid
+                .get_neighbors(spores.grid.get_bucket_from_pos(*spore_position))
+                .map(|neighbor| {
+                    let range = spores.bucket_range(spores.grid.bucket_index(neighbor));
+                    calc_force_from_bucket_simd(
+                        config,
+                        spore_configs,
+                        *spore_position,
+                        *spore_type,
+                        &xs[range.clone()],
+                        &ys[range.clone()],
+                        &spores.spore_types[range],
+                    )
+                })
+                .sum()
         })
-        .map(|(index, pos, speed, force)| {
-            let new_speed = speed * FRICTION + force;
-            (index, (modulo_position(pos + new_speed), new_speed))
+        .collect()
+}
+
+// Newton's third law: with symmetric spore configurations, the force of a spore on another one is
+// the opposite of the force of the other one on it, so every pair of spores is calculated once.
+// 1. per bucket, the pairs within the bucket and with its later neighbors (a higher bucket index)
+// 2. per bucket, the forces on its spores from itself, then those from its earlier neighbors
+// Both steps are parallel per bucket and sum in a fixed order, so the result is still deterministic.
+fn calc_pair_forces(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &SporesState,
+) -> Vec<Vector> {
+    let grid = &spores.grid;
+    // the forces on the spores of the bucket, followed by those on the spores of its later neighbors
+    let pair_forces: Vec<Vec<Vector>> = (0..grid.nr_buckets())
+        .into_par_iter()
+        .map(|bucket| {
+            let own = spores.bucket_range(bucket);
+            // an empty bucket exerts no forces, most of them when the spores are spread out
+            if own.is_empty() {
+                return Vec::new();
+            }
+            let neighbors: Vec<Range<usize>> = later_neighbors(grid, bucket)
+                .map(|neighbor| spores.bucket_range(neighbor))
+                .collect();
+            let mut forces =
+                vec![ZERO_VECTOR; own.len() + neighbors.iter().map(Range::len).sum::<usize>()];
+
+            for spore in own.clone() {
+                for other in spore + 1..own.end {
+                    let force = force_between(config, spore_configs, spores, spore, other);
+                    forces[spore - own.start] += force;
+                    forces[other - own.start] -= force;
+                }
+            }
+            let mut offset = own.len();
+            for neighbor in neighbors {
+                for spore in own.clone() {
+                    for other in neighbor.clone() {
+                        let force = force_between(config, spore_configs, spores, spore, other);
+                        forces[spore - own.start] += force;
+                        forces[offset + other - neighbor.start] -= force;
+                    }
+                }
+                offset += neighbor.len();
+            }
+            forces
         })
-        .unzip()
+        .collect();
+
+    // the spores are sorted by bucket, so every bucket writes its own part of the forces
+    let mut forces = vec![ZERO_VECTOR; spores.len()];
+    let mut bucket_forces = Vec::with_capacity(grid.nr_buckets());
+    let mut rest = forces.as_mut_slice();
+    for bucket in 0..grid.nr_buckets() {
+        let (own, later) = rest.split_at_mut(spores.bucket_range(bucket).len());
+        bucket_forces.push(own);
+        rest = later;
+    }
+    bucket_forces
+        .into_par_iter()
+        .enumerate()
+        .filter(|(_, own)| !own.is_empty())
+        .for_each(|(bucket, own)| {
+            own.copy_from_slice(&pair_forces[bucket][..own.len()]);
+            let earlier_neighbors = grid
+                .get_neighbors(grid.bucket_coord(bucket))
+                .map(|neighbor| grid.bucket_index(neighbor))
+                .filter(|earlier| *earlier < bucket && !pair_forces[*earlier].is_empty());
+            for earlier in earlier_neighbors {
+                // where the forces on the spores of this bucket are in those of the earlier neighbor
+                let offset = spores.bucket_range(earlier).len()
+                    + later_neighbors(grid, earlier)
+                        .take_while(|neighbor| *neighbor != bucket)
+                        .map(|neighbor| spores.bucket_range(neighbor).len())
+                        .sum::<usize>();
+                for (index, force) in own.iter_mut().enumerate() {
+                    *force += pair_forces[earlier][offset + index];
+                }
+            }
+        });
+    forces
+}
+
+// in the order of `Grid::get_neighbors`, every bucket once
+fn later_neighbors(grid: &Grid, bucket: usize) -> impl Iterator<Item = usize> + '_ {
+    grid.get_neighbors(grid.bucket_coord(bucket))
+        .map(move |neighbor| grid.bucket_index(neighbor))
+        .filter(move |neighbor| *neighbor > bucket)
 }
 
-fn modulo_position(position: Vector) -> Vector {
-    Vector {
-        x: (((position.x) % UNIVERSE_WIDTH) + UNIVERSE_WIDTH) % UNIVERSE_WIDTH,
-        y: ((position.y % UNIVERSE_HEIGHT) + UNIVERSE_HEIGHT) % UNIVERSE_HEIGHT,
+// the force of the other spore on the spore, zero out of reach
+fn force_between(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &SporesState,
+    spore: usize,
+    other: usize,
+) -> Vector {
+    let (spore_type, other_type) = (spores.spore_types[spore], spores.spore_types[other]);
+    let dist = to_calibrated_dist(config, spores.positions[other], spores.positions[spore]);
+    if dist.scalar <= spore_configs.force_reaches[(spore_type as usize, other_type as usize)] {
+        calculate_force(config, spore_configs, spore_type, other_type, dist)
+    } else {
+        ZERO_VECTOR
     }
 }
 
+pub fn update_spores_with_forces(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores: &mut SporesState,
+    range: Range<usize>,
+    forces: &[Vector],
+) {
+    let (grid, obstacles, time) = (&spores.grid, &spores.obstacles, spores.time);
+    spores.positions[range.clone()]
+        .par_iter_mut()
+        .zip(spores.speeds[range.clone()].par_iter_mut())
+        .zip(spores.ids[range.clone()].par_iter())
+        .zip(spores.spore_types[range].par_iter())
+        .zip(forces.par_iter())
+        .for_each(|((((pos, speed), id), spore_type), force)| {
+            let mass = spore_configs.masses[*spore_type as usize];
+            let damped_speed = damp(config, *speed, *id, time, mass);
+            let (new_pos, new_speed) = config.integrator.euler_step(
+                config,
+                *pos,
+                *speed,
+                damped_speed,
+                *force * (1.0 / mass),
+            );
+            let (new_pos, new_speed) = obstacles.collide(grid, *pos, new_pos, new_speed);
+            (*pos, *speed) = apply_boundaries(config, new_pos, new_speed);
+        });
+}
+
 // parallellizing with crayon slows this function down! even with DOD
 // TODO just pass neighbours?
 pub fn calculate_forces_on_spore(
+    config: &SimulationConfig,
     spore_configs: &SporeConfigs,
     spore: Vector,
+    spore_type: u8,
     spores: &SporesState,
     (horz, vert): BucketCoord,
 ) -> Vector {
@@ -74,22 +281,32 @@ pub fn calculate_forces_on_spore(
      * 2. calculate total force from bucket
      * 3. sum forces of neighbors
      */
-    get_neighbors(horz as isize, vert as isize)
-        .iter()
-        .map(|(neighb_horz, neighb_vert)| {
-            let bucket_positions = &spores.positions[*neighb_vert][*neighb_horz];
-            let bucket_spore_types = &spores.spore_types[*neighb_vert][*neighb_horz];
-            calc_force_from_bucket(spore_configs, spore, bucket_positions, bucket_spore_types)
+    spores
+        .grid
+        .get_neighbors((horz, vert))
+        .map(|neighbor| {
+            let range = spores.bucket_range(spores.grid.bucket_index(neighbor));
+            calc_force_from_bucket(
+                config,
+                spore_configs,
+                spore,
+                spore_type,
+                &spores.positions[range.clone()],
+                &spores.spore_types[range],
+            )
         })
         .sum()
 }
 
-fn calc_force_from_bucket(
+pub fn calc_force_from_bucket(
+    config: &SimulationConfig,
     spore_configs: &SporeConfigs,
     spore: Vector,
-    bucket_positions: &Vec<Vector>,
-    bucket_spore_types: &Vec<u8>,
+    spore_type: u8,
+    bucket_positions: &[Vector],
+    bucket_spore_types: &[u8],
 ) -> Vector {
+    let force_reaches = spore_configs.force_reaches.row(spore_type as usize);
     /*
      * 1. iter over spores in bucket
      * 2. calc force of bucket spore on given spore
@@ -97,69 +314,68 @@ fn calc_force_from_bucket(
      * 4. sum forces
      */
     (0..bucket_positions.len())
-        .into_iter()
         .map(|bucket_index| {
             (
                 bucket_index,
-                to_calibrated_dist(bucket_positions[bucket_index as usize], spore),
+                to_calibrated_dist(config, bucket_positions[bucket_index], spore),
             )
         })
         .filter(|(bucket_index, dist)| {
-            dist.scalar
-                <= spore_configs.force_reaches[bucket_spore_types[*bucket_index as usize] as usize]
+            dist.scalar <= force_reaches[bucket_spore_types[*bucket_index] as usize]
         })
         .map(|(bucket_index, dist)| {
             calculate_force(
+                config,
                 spore_configs,
-                bucket_spore_types[bucket_index as usize],
+                spore_type,
+                bucket_spore_types[bucket_index],
                 dist,
             )
         })
         .sum()
 }
 
-// the force function follows this function in terms of distance: either |\./\ or |\.\/
-pub fn calculate_force(spore_configs: &SporeConfigs, spore_type: u8, dist: Dist) -> Vector {
-    let repulsion_dist = spore_configs.repulsion_dists[spore_type as usize];
+// the shape of the force in terms of distance is given by the configured force law
+pub fn calculate_force(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spore_type: u8,
+    other_type: u8,
+    dist: Dist,
+) -> Vector {
     if dist.scalar < 0.000001 {
-        ZERO_VECTOR // because probably own spore (location)
-    } else if dist.scalar < repulsion_dist {
-        let repulsion_force =
-            (dist.scalar - repulsion_dist).powi(2) * REPULSION_AMPLITUDE / repulsion_dist.powi(2);
-
-        dist.vector * repulsion_force
-    } else {
-        scale_force(&spore_configs, spore_type as usize, dist)
+        return ZERO_VECTOR; // because probably own spore (location)
     }
-}
-
-fn scale_force(spore_configs: &SporeConfigs, spore_type: usize, dist: Dist) -> Vector {
-    let repulsion_dist = spore_configs.repulsion_dists[spore_type];
-    let not_repulsion_dist = dist.scalar - repulsion_dist;
-    let net_force_reach = spore_configs.force_reaches[spore_type] - repulsion_dist;
-    let dist_from_force_reach_center = (net_force_reach - not_repulsion_dist).abs();
-    let scale = dist_from_force_reach_center / net_force_reach / 2.0;
-    let factor = spore_configs.force_factors[spore_type];
+    let (spore_type, other_type) = (spore_type as usize, other_type as usize);
+    let params = PairParams {
+        repulsion_dist: spore_configs.repulsion_dists[(spore_type, other_type)],
+        force_factor: spore_configs.force_factors[(spore_type, other_type)],
+        force_reach: spore_configs.force_reaches[(spore_type, other_type)],
+        repulsion_amplitude: config.repulsion_amplitude,
+    };
+    let force = config.force_law.evaluate(dist.scalar, &params);
 
-    dist.vector * (factor * scale / dist.scalar)
+    dist.vector * (force / dist.scalar)
 }
 
 pub struct Dist {
-    vector: Vector,
-    scalar: f32,
+    pub(crate) vector: Vector,
+    pub(crate) scalar: f32,
 }
 
-fn to_calibrated_dist(other: Vector, spore: Vector) -> Dist {
+pub(crate) fn to_calibrated_dist(config: &SimulationConfig, other: Vector, spore: Vector) -> Dist {
     let uncalibrated_dist = other - spore;
+    let (universe_width, universe_height) = (config.universe_width(), config.universe_height());
 
-    // recalibrate to account for wrap-around
-    let x = if uncalibrated_dist.x.abs() >= UNIVERSE_WIDTH - MAX_FORCE_REACH {
-        uncalibrated_dist.x - UNIVERSE_WIDTH * uncalibrated_dist.x.signum()
+    // recalibrate to account for wrap-around: the nearest copy of the other spore (minimum image)
+    let boundaries = config.boundaries;
+    let x = if boundaries.x.is_periodic() && uncalibrated_dist.x.abs() > universe_width / 2.0 {
+        uncalibrated_dist.x - universe_width * uncalibrated_dist.x.signum()
     } else {
         uncalibrated_dist.x
     };
-    let y = if uncalibrated_dist.y.abs() >= UNIVERSE_HEIGHT - MAX_FORCE_REACH {
-        uncalibrated_dist.y - UNIVERSE_HEIGHT * uncalibrated_dist.y.signum()
+    let y = if boundaries.y.is_periodic() && uncalibrated_dist.y.abs() > universe_height / 2.0 {
+        uncalibrated_dist.y - universe_height * uncalibrated_dist.y.signum()
     } else {
         uncalibrated_dist.y
     };
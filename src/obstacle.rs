@@ -0,0 +1,387 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    bucket::Grid,
+    configuration::SimulationConfig,
+    image::{GrayImage, ImageError},
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// A static obstacle as written in the configuration, in the coordinates of the universe.
+/// Obstacles don't wrap around the edges of the universe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObstacleConfig {
+    Circle {
+        center: Vector,
+        radius: f32,
+    },
+    /// Axis aligned, from the top left corner `min` to the bottom right corner `max`.
+    Rectangle {
+        min: Vector,
+        max: Vector,
+    },
+    /// A wall of `thickness` with rounded ends.
+    Segment {
+        from: Vector,
+        to: Vector,
+        thickness: f32,
+    },
+    /// A PNG stretched over the whole universe: its dark, opaque pixels are solid.
+    Mask {
+        path: String,
+    },
+}
+
+/// A solid shape in the universe, see `ObstacleConfig`. A mask holds its pixels instead of its file,
+/// so a snapshot doesn't need the file anymore.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Obstacle {
+    Circle {
+        center: Vector,
+        radius: f32,
+    },
+    Rectangle {
+        min: Vector,
+        max: Vector,
+    },
+    Segment {
+        from: Vector,
+        to: Vector,
+        thickness: f32,
+    },
+    Mask(Mask),
+}
+
+/// The solid pixels of a mask, row by row from the top left.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mask {
+    pub width: usize,
+    pub height: usize,
+    pub solid: Vec<bool>,
+    // the size of a pixel in the universe
+    pub pixel_width: f32,
+    pub pixel_height: f32,
+}
+
+impl Mask {
+    /// Loads a PNG and stretches it over the universe.
+    pub fn load(config: &SimulationConfig, path: &Path) -> Result<Mask, ImageError> {
+        let image = GrayImage::load(path)?;
+        let (width, height) = (image.width, image.height);
+        Ok(Mask {
+            width,
+            height,
+            solid: image
+                .pixels
+                .iter()
+                .map(|(luma, alpha)| *luma < 128 && *alpha >= 128)
+                .collect(),
+            pixel_width: config.universe_width() / width as f32,
+            pixel_height: config.universe_height() / height as f32,
+        })
+    }
+
+    fn pixel(&self, position: Vector) -> Option<(usize, usize)> {
+        let (x, y) = (
+            (position.x / self.pixel_width).floor(),
+            (position.y / self.pixel_height).floor(),
+        );
+        let inside =
+            x >= 0.0 && y >= 0.0 && (x as usize) < self.width && (y as usize) < self.height;
+        inside.then_some((x as usize, y as usize))
+    }
+
+    pub fn is_solid(&self, position: Vector) -> bool {
+        self.pixel(position)
+            .is_some_and(|(x, y)| self.solid[y * self.width + x])
+    }
+}
+
+impl Obstacle {
+    // the top left and bottom right corners of the box around the obstacle
+    fn bounds(&self) -> (Vector, Vector) {
+        match self {
+            Obstacle::Circle { center, radius } => (
+                Vector {
+                    x: center.x - radius,
+                    y: center.y - radius,
+                },
+                Vector {
+                    x: center.x + radius,
+                    y: center.y + radius,
+                },
+            ),
+            Obstacle::Rectangle { min, max } => (*min, *max),
+            Obstacle::Segment {
+                from,
+                to,
+                thickness,
+            } => (
+                Vector {
+                    x: from.x.min(to.x) - thickness / 2.0,
+                    y: from.y.min(to.y) - thickness / 2.0,
+                },
+                Vector {
+                    x: from.x.max(to.x) + thickness / 2.0,
+                    y: from.y.max(to.y) + thickness / 2.0,
+                },
+            ),
+            Obstacle::Mask(mask) => (
+                ZERO_VECTOR,
+                Vector {
+                    x: mask.width as f32 * mask.pixel_width,
+                    y: mask.height as f32 * mask.pixel_height,
+                },
+            ),
+        }
+    }
+
+    // the indexes of the buckets the obstacle overlaps
+    fn buckets(&self, grid: &Grid) -> Vec<usize> {
+        let buckets_in = |(min, max): (Vector, Vector)| {
+            let ((min_horz, min_vert), (max_horz, max_vert)) =
+                (grid.get_bucket_from_pos(min), grid.get_bucket_from_pos(max));
+            (min_vert..=max_vert).flat_map(move |vert| {
+                (min_horz..=max_horz).map(move |horz| grid.bucket_index((horz, vert)))
+            })
+        };
+        let mut buckets: Vec<usize> = match self {
+            // only the buckets with solid pixels
+            Obstacle::Mask(mask) => (0..mask.height)
+                .flat_map(|y| (0..mask.width).map(move |x| (x, y)))
+                .filter(|(x, y)| mask.solid[y * mask.width + x])
+                .flat_map(|(x, y)| {
+                    let min = Vector {
+                        x: x as f32 * mask.pixel_width,
+                        y: y as f32 * mask.pixel_height,
+                    };
+                    let max = Vector {
+                        x: min.x + mask.pixel_width,
+                        y: min.y + mask.pixel_height,
+                    };
+                    buckets_in((min, max))
+                })
+                .collect(),
+            _ => buckets_in(self.bounds()).collect(),
+        };
+        buckets.sort_unstable();
+        buckets.dedup();
+        buckets
+    }
+
+    /// Whether a spore moving from `from` to `to` runs into the obstacle, with the outward normal
+    /// of the surface it hits. The normal of a mask is unknown: `ZERO_VECTOR`.
+    pub fn hit(&self, from: Vector, to: Vector) -> Option<Vector> {
+        match self {
+            Obstacle::Circle { center, radius } => {
+                let outward = to - *center;
+                (length(outward) < *radius).then(|| normalized(outward))
+            }
+            Obstacle::Rectangle { min, max } => {
+                let inside = min.x < to.x && to.x < max.x && min.y < to.y && to.y < max.y;
+                inside.then(|| {
+                    // out through the nearest side
+                    [
+                        (to.x - min.x, Vector { x: -1.0, y: 0.0 }),
+                        (max.x - to.x, Vector { x: 1.0, y: 0.0 }),
+                        (to.y - min.y, Vector { x: 0.0, y: -1.0 }),
+                        (max.y - to.y, Vector { x: 0.0, y: 1.0 }),
+                    ]
+                    .iter()
+                    .min_by(|(dist, _), (other_dist, _)| dist.total_cmp(other_dist))
+                    .map(|(_, normal)| *normal)
+                    .unwrap_or(ZERO_VECTOR)
+                })
+            }
+            Obstacle::Segment {
+                from: start,
+                to: end,
+                thickness,
+            } => {
+                let along = *end - *start;
+                let side = Vector {
+                    x: -along.y,
+                    y: along.x,
+                };
+                // towards the side the spore comes from
+                let side = if dot(from - *start, side) < 0.0 {
+                    side * -1.0
+                } else {
+                    side
+                };
+                let outward = to - closest_on_segment(*start, *end, to);
+                if length(outward) < thickness / 2.0 {
+                    Some(if outward == ZERO_VECTOR {
+                        normalized(side)
+                    } else {
+                        normalized(outward)
+                    })
+                } else {
+                    // a fast spore can jump over a thin wall
+                    crosses(from, to, *start, *end).then(|| normalized(side))
+                }
+            }
+            Obstacle::Mask(mask) => mask.is_solid(to).then_some(ZERO_VECTOR),
+        }
+    }
+}
+
+// the point of the segment from `start` to `end` nearest to `position`
+fn closest_on_segment(start: Vector, end: Vector, position: Vector) -> Vector {
+    let along = end - start;
+    let squared_length = dot(along, along);
+    if squared_length == 0.0 {
+        return start;
+    }
+    let fraction = (dot(position - start, along) / squared_length).clamp(0.0, 1.0);
+    start + along * fraction
+}
+
+// whether the segments from `a` to `b` and from `c` to `d` cross each other
+fn crosses(a: Vector, b: Vector, c: Vector, d: Vector) -> bool {
+    let side = |p: Vector, q: Vector, r: Vector| cross(q - p, r - p);
+    side(a, b, c) * side(a, b, d) < 0.0 && side(c, d, a) * side(c, d, b) < 0.0
+}
+
+fn dot(a: Vector, b: Vector) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+fn cross(a: Vector, b: Vector) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+pub(crate) fn length(vector: Vector) -> f32 {
+    vector.x.hypot(vector.y)
+}
+
+pub(crate) fn normalized(vector: Vector) -> Vector {
+    let length = length(vector);
+    if length == 0.0 {
+        ZERO_VECTOR
+    } else {
+        vector * (1.0 / length)
+    }
+}
+
+/// The obstacles of a run, indexed per bucket like the spores: the obstacles overlapping bucket `b`
+/// are `obstacles[obstacle_indexes[bucket_starts[b]..bucket_starts[b + 1]]]`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Obstacles {
+    pub obstacles: Vec<Obstacle>,
+    bucket_starts: Vec<usize>,
+    obstacle_indexes: Vec<usize>,
+}
+
+impl Obstacles {
+    /// The obstacles of the configuration, with the masks loaded from their files.
+    pub fn load(config: &SimulationConfig, grid: &Grid) -> Result<Obstacles, ImageError> {
+        let obstacles = config
+            .obstacles
+            .iter()
+            .map(|obstacle| {
+                Ok(match obstacle.clone() {
+                    ObstacleConfig::Circle { center, radius } => {
+                        Obstacle::Circle { center, radius }
+                    }
+                    ObstacleConfig::Rectangle { min, max } => Obstacle::Rectangle { min, max },
+                    ObstacleConfig::Segment {
+                        from,
+                        to,
+                        thickness,
+                    } => Obstacle::Segment {
+                        from,
+                        to,
+                        thickness,
+                    },
+                    ObstacleConfig::Mask { path } => {
+                        Obstacle::Mask(Mask::load(config, Path::new(&path))?)
+                    }
+                })
+            })
+            .collect::<Result<_, ImageError>>()?;
+        Ok(Obstacles::new(obstacles, grid))
+    }
+
+    pub fn new(obstacles: Vec<Obstacle>, grid: &Grid) -> Obstacles {
+        let mut per_bucket = vec![Vec::new(); grid.nr_buckets()];
+        for (index, obstacle) in obstacles.iter().enumerate() {
+            for bucket in obstacle.buckets(grid) {
+                per_bucket[bucket].push(index);
+            }
+        }
+        let mut bucket_starts = vec![0];
+        for indexes in &per_bucket {
+            bucket_starts.push(bucket_starts.last().unwrap() + indexes.len());
+        }
+
+        Obstacles {
+            obstacles,
+            bucket_starts,
+            obstacle_indexes: per_bucket.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.obstacles.is_empty()
+    }
+
+    /// Whether the obstacles are indexed for the buckets of the grid.
+    pub fn is_indexed_for(&self, grid: &Grid) -> bool {
+        self.is_empty()
+            || (self.bucket_starts.len() == grid.nr_buckets() + 1
+                && self.obstacle_indexes.len() == *self.bucket_starts.last().unwrap()
+                && self
+                    .obstacle_indexes
+                    .iter()
+                    .all(|index| *index < self.obstacles.len()))
+    }
+
+    fn in_bucket_of(&self, grid: &Grid, position: Vector) -> impl Iterator<Item = &Obstacle> {
+        let indexes = if self.is_empty() {
+            &[]
+        } else {
+            let bucket = grid.bucket_index(grid.get_bucket_from_pos(position));
+            &self.obstacle_indexes[self.bucket_starts[bucket]..self.bucket_starts[bucket + 1]]
+        };
+        indexes.iter().map(move |index| &self.obstacles[*index])
+    }
+
+    pub fn contains(&self, grid: &Grid, position: Vector) -> bool {
+        self.in_bucket_of(grid, position)
+            .any(|obstacle| obstacle.hit(position, position).is_some())
+    }
+
+    /// A spore moving from `from` to `to` that runs into an obstacle stays at `from`,
+    /// and bounces off: the part of its speed into the obstacle is reversed.
+    pub fn collide(
+        &self,
+        grid: &Grid,
+        from: Vector,
+        to: Vector,
+        speed: Vector,
+    ) -> (Vector, Vector) {
+        if self.is_empty() {
+            return (to, speed);
+        }
+        let normal = self
+            .in_bucket_of(grid, to)
+            .chain(self.in_bucket_of(grid, from))
+            .find_map(|obstacle| obstacle.hit(from, to));
+        match normal {
+            None => (to, speed),
+            // the mask doesn't know which way is out
+            Some(normal) if normal == ZERO_VECTOR => (from, speed * -1.0),
+            Some(normal) => {
+                let into = dot(speed, normal);
+                if into < 0.0 {
+                    (from, speed - normal * (2.0 * into))
+                } else {
+                    (from, speed)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    bond::roll, configuration::SimulationConfig, movement_calculator::to_calibrated_dist,
+    spore::SporesState,
+};
+
+// the reaction rolls are independent of the bond rolls of the same pair
+const REACTION_SALT: u64 = 0x5EAC_7105;
+
+/// Two spores of the `reactants` types that stay close together turn into the `products` types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReactionRule {
+    /// The types of the two spores, in any order.
+    pub reactants: [u8; 2],
+    /// Two types, one per reactant (`A + B -> C + B` for catalysis), or a single type for the first
+    /// reactant while the second one is used up (`A + A -> B`).
+    pub products: Vec<u8>,
+    /// The spores react with `probability` per tick, once they have been closer than `dist`
+    /// for a time of `contact_time` (ticks times dt).
+    pub dist: f32,
+    #[serde(default)]
+    pub contact_time: f32,
+    pub probability: f32,
+}
+
+impl ReactionRule {
+    pub fn is_valid(&self, number_of_configs: usize) -> bool {
+        (1..=2).contains(&self.products.len())
+            && self
+                .reactants
+                .iter()
+                .chain(&self.products)
+                .all(|spore_type| (*spore_type as usize) < number_of_configs)
+            && self.dist > 0.0
+            && self.contact_time >= 0.0
+            && (0.0..=1.0).contains(&self.probability)
+    }
+
+    // the spores in the order of the reactants, `None` if their types don't match
+    fn reactant_order(&self, types: [u8; 2]) -> Option<bool> {
+        if types == self.reactants {
+            Some(false)
+        } else if types == [self.reactants[1], self.reactants[0]] {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// Two spores that are within the distance of a rule, since `since`.
+/// The ids are in the order of the reactants of the rule, ascending for reactants of the same type.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    pub rule: u32,
+    pub ids: [u32; 2],
+    pub since: f64,
+}
+
+/// The longest distance spores react at, the buckets are at least as large.
+pub fn longest_reaction_dist(config: &SimulationConfig) -> f32 {
+    config
+        .reactions
+        .iter()
+        .fold(0.0, |max, rule| max.max(rule.dist))
+}
+
+/// Finds the spores within the distance of a rule, and lets those that have been in contact for
+/// long enough react. A spore reacts at most once per tick, the reactions are counted per rule.
+/// Call after sorting the spores into buckets, the spores that are used up are removed.
+pub(crate) fn update_reactions(config: &SimulationConfig, spores: &mut SporesState) {
+    let rules = &config.reactions;
+    let time = spores.time;
+    let previous: HashMap<(u32, [u32; 2]), f64> = spores
+        .contacts
+        .iter()
+        .map(|contact| ((contact.rule, contact.ids), contact.since))
+        .collect();
+
+    // every (rule, spores in the order of its reactants) within reach, in a fixed order
+    let state = &*spores;
+    let grid = &state.grid;
+    let in_reach: Vec<(u32, [usize; 2])> = (0..state.len())
+        .into_par_iter()
+        .flat_map_iter(|spore| {
+            let position = state.positions[spore];
+            grid.get_neighbors(grid.get_bucket_from_pos(position))
+                .flat_map(move |neighbor| state.bucket_range(grid.bucket_index(neighbor)))
+                .filter(move |other| *other > spore)
+                .flat_map(move |other| {
+                    let dist = to_calibrated_dist(config, state.positions[other], position).scalar;
+                    let types = [state.spore_types[spore], state.spore_types[other]];
+                    rules.iter().enumerate().filter_map(move |(index, rule)| {
+                        let swapped = rule.reactant_order(types)?;
+                        (dist < rule.dist).then_some((
+                            index as u32,
+                            if swapped {
+                                [other, spore]
+                            } else {
+                                [spore, other]
+                            },
+                        ))
+                    })
+                })
+        })
+        .collect();
+
+    let mut reacted = vec![false; state.len()];
+    let mut used_up = vec![false; state.len()];
+    let mut counts = vec![0; rules.len()];
+    let mut contacts = Vec::with_capacity(in_reach.len());
+    let mut new_types = state.spore_types.clone();
+    for (index, [mut a, mut b]) in in_reach {
+        let rule = &rules[index as usize];
+        // two reactants of the same type keep their contact whatever their order in the buckets
+        if rule.reactants[0] == rule.reactants[1] && state.ids[a] > state.ids[b] {
+            (a, b) = (b, a);
+        }
+        let ids = [state.ids[a], state.ids[b]];
+        let since = previous.get(&(index, ids)).copied().unwrap_or(time);
+        let reacts = !reacted[a]
+            && !reacted[b]
+            && time - since >= rule.contact_time as f64
+            && roll(config, REACTION_SALT ^ index as u64, ids, time) <= rule.probability as f64;
+        if !reacts {
+            contacts.push(Contact {
+                rule: index,
+                ids,
+                since,
+            });
+            continue;
+        }
+        reacted[a] = true;
+        reacted[b] = true;
+        counts[index as usize] += 1;
+        new_types[a] = rule.products[0];
+        match rule.products.get(1) {
+            Some(product) => new_types[b] = *product,
+            None => used_up[b] = true,
+        }
+    }
+
+    // the contacts of the spores that reacted start over with their new types
+    let indexes = state.indexes_by_id();
+    contacts.retain(|contact| contact.ids.iter().all(|id| !reacted[indexes[*id as usize]]));
+    spores.spore_types = new_types;
+    spores.contacts = contacts;
+    spores.reaction_counts = counts;
+    if used_up.contains(&true) {
+        spores.retain_indexes(|index| !used_up[index]);
+        spores.sort_into_buckets();
+    }
+}
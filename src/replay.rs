@@ -0,0 +1,325 @@
+use crate::simulation::{draw_selection, draw_spores, palette, rgb, spore_at, View};
+use ggez::{
+    self,
+    context::Context,
+    event::{self, MouseButton},
+    graphics::{self, Color, DrawMode, Mesh, Rect},
+    input::keyboard::{KeyCode, KeyInput},
+    GameResult,
+};
+use pycniospores::{trajectory::TrajectoryReader, vector::Vector};
+
+const MIN_PLAYBACK_SPEED: f32 = 1.0 / 16.0;
+const MAX_PLAYBACK_SPEED: f32 = 64.0;
+const SCRUB_STEP: f32 = 0.1;
+const TIMELINE_MARGIN: f32 = 10.0;
+const TIMELINE_HEIGHT: f32 = 12.0;
+
+/// Plays a recorded trajectory: the "simulate, then watch later" option.
+pub struct Replay {
+    reader: TrajectoryReader,
+    frame_index: usize,
+    playing: bool,
+    // frames per update, below 1.0 frames are shown during several updates
+    speed: f32,
+    progress: f32,
+    view: View,
+    palette: Vec<Color>,
+    radii: Vec<f32>,
+    // the id of the spore that was clicked, followed from frame to frame
+    selected: Option<u32>,
+    // the digits of the tick to go to, typed before pressing enter
+    tick_input: String,
+}
+
+impl Replay {
+    pub fn new(
+        ctx: &mut Context,
+        reader: TrajectoryReader,
+        window_width: f32,
+        window_height: f32,
+    ) -> GameResult<Replay> {
+        ctx.gfx.add_font(
+            "DejaVu",
+            graphics::FontData::from_path(ctx, "/DejaVuSerif.ttf")?,
+        );
+
+        let header = reader.header();
+        let view = View::new(
+            window_width,
+            window_height,
+            header.universe_width,
+            header.universe_height,
+        );
+        let palette = palette(header.spore_configs.number_of_configs());
+        let radii = header.spore_configs.radii.clone();
+        Ok(Replay {
+            reader,
+            frame_index: 0,
+            playing: true,
+            speed: 1.0,
+            progress: 0.0,
+            view,
+            palette,
+            radii,
+            selected: None,
+            tick_input: String::new(),
+        })
+    }
+
+    fn last_frame_index(&self) -> usize {
+        self.reader.nr_frames().saturating_sub(1)
+    }
+
+    fn go_to_frame(&mut self, frame_index: isize) {
+        self.frame_index = frame_index.clamp(0, self.last_frame_index() as isize) as usize;
+        self.progress = 0.0;
+    }
+
+    fn step(&mut self, nr_of_frames: isize) {
+        self.playing = false;
+        self.go_to_frame(self.frame_index as isize + nr_of_frames);
+    }
+
+    fn scrub(&mut self, fraction: f32) {
+        self.go_to_frame(
+            (fraction.clamp(0.0, 1.0) * self.last_frame_index() as f32).round() as isize,
+        );
+    }
+
+    /// Goes to the last frame recorded at or before the typed tick.
+    fn go_to_typed_tick(&mut self) {
+        let Ok(tick) = std::mem::take(&mut self.tick_input).parse() else {
+            return;
+        };
+        match self.reader.frame_index_at_tick(tick) {
+            Ok(frame_index) => {
+                self.playing = false;
+                self.go_to_frame(frame_index as isize);
+            }
+            Err(err) => eprintln!("Could not go to tick {}: {}", tick, err),
+        }
+    }
+
+    fn timeline(ctx: &Context) -> Rect {
+        let (width, height) = ctx.gfx.drawable_size();
+        Rect::new(
+            TIMELINE_MARGIN,
+            height - TIMELINE_MARGIN - TIMELINE_HEIGHT,
+            width - 2.0 * TIMELINE_MARGIN,
+            TIMELINE_HEIGHT,
+        )
+    }
+
+    fn scrub_to_mouse(&mut self, ctx: &Context, x: f32) {
+        let timeline = Replay::timeline(ctx);
+        self.scrub((x - timeline.x) / timeline.w);
+    }
+}
+
+impl event::EventHandler<ggez::GameError> for Replay {
+    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+        if self.playing {
+            self.progress += self.speed;
+            let nr_of_frames = self.progress.floor();
+            self.progress -= nr_of_frames;
+            self.frame_index = usize::min(
+                self.frame_index + nr_of_frames as usize,
+                self.last_frame_index(),
+            );
+            if self.frame_index == self.last_frame_index() {
+                self.playing = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
+        let page = (self.reader.nr_frames() as f32 * SCRUB_STEP).ceil() as isize;
+        match input.keycode.unwrap_or(KeyCode::A) {
+            KeyCode::Escape => {
+                ctx.request_quit();
+            }
+            KeyCode::Space => {
+                if self.frame_index == self.last_frame_index() {
+                    self.go_to_frame(0);
+                }
+                self.playing = !self.playing;
+            }
+            KeyCode::RBracket => self.step(1),
+            KeyCode::LBracket => self.step(-1),
+            KeyCode::PageDown => self.go_to_frame(self.frame_index as isize + page),
+            KeyCode::PageUp => self.go_to_frame(self.frame_index as isize - page),
+            KeyCode::Return | KeyCode::NumpadEnter => self.go_to_typed_tick(),
+            KeyCode::Back => {
+                self.tick_input.pop();
+            }
+            KeyCode::Home => self.go_to_frame(0),
+            KeyCode::End => self.go_to_frame(self.last_frame_index() as isize),
+            KeyCode::Equals => {
+                self.speed = f32::min(MAX_PLAYBACK_SPEED, self.speed * 2.0);
+            }
+            KeyCode::Minus => {
+                self.speed = f32::max(MIN_PLAYBACK_SPEED, self.speed / 2.0);
+            }
+            keycode => self.view.handle_key(keycode),
+        }
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        // u32::MAX has 10 digits
+        if character.is_ascii_digit() && self.tick_input.len() < 10 {
+            self.tick_input.push(character);
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        let timeline = Replay::timeline(ctx);
+        if button == MouseButton::Left && y >= timeline.y - TIMELINE_MARGIN {
+            self.scrub_to_mouse(ctx, x);
+        } else if button == MouseButton::Left {
+            if let Ok(frame) = self.reader.frame(self.frame_index) {
+                let spores = frame.spores.iter().map(|spore| {
+                    (
+                        spore.id,
+                        Vector {
+                            x: spore.x,
+                            y: spore.y,
+                        },
+                        spore.spore_type,
+                    )
+                });
+                self.selected = spore_at(&self.view, &self.radii, x, y, spores);
+            }
+        }
+        Ok(())
+    }
+
+    fn mouse_motion_event(
+        &mut self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        _dx: f32,
+        _dy: f32,
+    ) -> GameResult {
+        let timeline = Replay::timeline(ctx);
+        if ctx.mouse.button_pressed(MouseButton::Left) && y >= timeline.y - TIMELINE_MARGIN {
+            self.scrub_to_mouse(ctx, x);
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.1, 0.2, 0.3, 1.0]));
+
+        let (nr_of_spores, tick) = match self.reader.frame(self.frame_index) {
+            Ok(frame) => {
+                draw_spores(
+                    ctx,
+                    &mut canvas,
+                    &self.view,
+                    &self.palette,
+                    &self.radii,
+                    frame.spores.iter().map(|spore| {
+                        (
+                            Vector {
+                                x: spore.x,
+                                y: spore.y,
+                            },
+                            spore.spore_type,
+                        )
+                    }),
+                )?;
+                if let Some(id) = self.selected {
+                    let spore = frame.spores.iter().find(|spore| spore.id == id);
+                    draw_selection(
+                        ctx,
+                        &mut canvas,
+                        &self.view,
+                        &self.radii,
+                        id,
+                        spore.map(|spore| {
+                            (
+                                Vector {
+                                    x: spore.x,
+                                    y: spore.y,
+                                },
+                                spore.spore_type,
+                            )
+                        }),
+                    )?;
+                }
+                (frame.spores.len(), frame.tick)
+            }
+            Err(err) => {
+                eprintln!("Could not read frame {}: {}", self.frame_index, err);
+                self.playing = false;
+                (0, 0)
+            }
+        };
+        draw_timeline(ctx, &mut canvas, self)?;
+        show_replay_numbers(&mut canvas, self, nr_of_spores, tick)?;
+
+        canvas.finish(ctx)?;
+        Ok(())
+    }
+}
+
+fn draw_timeline(ctx: &mut Context, canvas: &mut graphics::Canvas, replay: &Replay) -> GameResult {
+    let timeline = Replay::timeline(ctx);
+    let played = replay.frame_index as f32 / usize::max(1, replay.last_frame_index()) as f32;
+    let mut played_part = timeline;
+    played_part.w *= played;
+
+    let mut mesh_builder = graphics::MeshBuilder::new();
+    mesh_builder
+        .rectangle(DrawMode::fill(), timeline, rgb(34, 49, 63))?
+        .rectangle(DrawMode::fill(), played_part, rgb(29, 227, 234))?;
+    let mesh = mesh_builder.build();
+    canvas.draw(&Mesh::from_data(&ctx.gfx, mesh), graphics::DrawParam::new());
+    Ok(())
+}
+
+fn show_replay_numbers(
+    canvas: &mut graphics::Canvas,
+    replay: &Replay,
+    nr_of_spores: usize,
+    tick: u32,
+) -> GameResult {
+    // Text is drawn from the top-left corner.
+    let offset = 10.0;
+    let dest_point = ggez::glam::Vec2::new(offset, offset);
+    canvas.draw(
+        graphics::Text::new(format!(
+            "#spores: {}\nFrame: {}/{}\nTick: {}\nSpeed: x{}{}\nZoom: x{:.2}\nCoords: {:?}{}",
+            nr_of_spores,
+            replay.frame_index + 1,
+            replay.reader.nr_frames(),
+            tick,
+            replay.speed,
+            if replay.playing { "" } else { " (paused)" },
+            replay.view.zoom,
+            replay.view.position,
+            if replay.tick_input.is_empty() {
+                String::new()
+            } else {
+                format!("\nGo to tick: {}", replay.tick_input)
+            },
+        ))
+        .set_font("DejaVu")
+        .set_scale(36.0),
+        dest_point,
+    );
+
+    Ok(())
+}
@@ -1,57 +1,150 @@
-use crate::{
-    bucket::get_buckets,
-    configuration::{UNIVERSE_HEIGHT, UNIVERSE_WIDTH},
-    generators::{generate_spore_configs, generate_spores},
-    spore::{SporeConfigs, SporesState},
-    spore_mover::move_spores,
-    HEIGHT_RATIO, MAX_ZOOM, MIN_ZOOM, MOVE_INCREMENT, WIDTH_RATIO, ZOOM_SPEED,
-};
+use crate::{MAX_ZOOM, MOVE_INCREMENT, ZOOM_SPEED};
 use ggez::{
     self,
     context::Context,
-    event,
+    event::{self, MouseButton},
     glam::Vec2,
     graphics::{self, Color, Mesh},
     input::keyboard::{KeyCode, KeyInput},
     mint::{Point2, Vector2},
     GameResult,
 };
+use pycniospores::{
+    obstacle::{Obstacle, Obstacles},
+    snapshot::{load_snapshot, save_snapshot},
+    trajectory::TrajectoryRecorder,
+    vector::Vector,
+    World,
+};
+use std::path::PathBuf;
 
 pub struct Simulation {
-    nr_of_spores: u16,
+    pub world: World,
+    snapshot_path: PathBuf,
+    recorder: Option<TrajectoryRecorder>,
     paused: bool,
-    tick: u32,
-    pub spore_configs: SporeConfigs,
-    spores: SporesState,
-    view_position: Point2<f32>,
-    zoom: f32,
+    view: View,
+    palette: Vec<Color>,
+    // the obstacles don't move, so their mesh is only built with the world
+    obstacles_mesh: Option<Mesh>,
+    // the id of the spore that was clicked
+    selected: Option<u32>,
+}
+
+/// The part of the universe shown in the window, moved with the arrows and zoomed with , and .
+pub struct View {
+    pub position: Point2<f32>,
+    pub zoom: f32,
+    window_width: f32,
+    window_height: f32,
+    universe_width: f32,
+    universe_height: f32,
 }
 
 impl Simulation {
-    pub fn new(ctx: &mut Context, nr_of_spores: u16) -> GameResult<Simulation> {
+    pub fn new(
+        ctx: &mut Context,
+        world: World,
+        snapshot_path: PathBuf,
+        recorder: Option<TrajectoryRecorder>,
+    ) -> GameResult<Simulation> {
         ctx.gfx.add_font(
             "DejaVu",
             graphics::FontData::from_path(ctx, "/DejaVuSerif.ttf")?,
         );
 
         let s = Simulation {
+            view: View::for_world(&world),
+            palette: palette(world.spore_configs().number_of_configs()),
+            obstacles_mesh: obstacles_mesh(ctx, world.obstacles())?,
+            world,
+            snapshot_path,
+            recorder,
             paused: false,
-            nr_of_spores: nr_of_spores,
-            tick: 0,
-            spore_configs: generate_spore_configs(),
-            spores: generate_spores(nr_of_spores),
-            view_position: Point2 { x: 0.0, y: 0.0 },
-            zoom: 1.0,
+            selected: None,
         };
         Ok(s)
     }
+
+    // a failing save or load shouldn't end the simulation
+    fn save_snapshot(&self) {
+        match save_snapshot(&self.world, &self.snapshot_path) {
+            Ok(()) => println!(
+                "Saved tick {} to {}",
+                self.world.tick(),
+                self.snapshot_path.display()
+            ),
+            Err(err) => eprintln!("Could not save {}: {}", self.snapshot_path.display(), err),
+        }
+    }
+
+    fn load_snapshot(&mut self, ctx: &Context) -> GameResult {
+        match load_snapshot(&self.snapshot_path) {
+            Ok(world) => {
+                // the recording can only go forward in time
+                self.finish_recording();
+                self.view = View::for_world(&world);
+                self.palette = palette(world.spore_configs().number_of_configs());
+                self.obstacles_mesh = obstacles_mesh(ctx, world.obstacles())?;
+                self.world = world;
+                println!(
+                    "Loaded tick {} from {}",
+                    self.world.tick(),
+                    self.snapshot_path.display()
+                );
+            }
+            Err(err) => eprintln!("Could not load {}: {}", self.snapshot_path.display(), err),
+        }
+        Ok(())
+    }
+
+    fn record(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = recorder.record(&self.world) {
+                eprintln!("Stopped recording: {}", err);
+                self.recorder = None;
+            }
+        }
+    }
+
+    fn finish_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.finish() {
+                Ok(()) => println!("Stopped recording at tick {}", self.world.tick()),
+                Err(err) => eprintln!("Could not finish the recording: {}", err),
+            }
+        }
+    }
 }
 
 impl event::EventHandler<ggez::GameError> for Simulation {
     fn update(&mut self, _ctx: &mut Context) -> GameResult {
         if !self.paused {
-            move_spores(&self.spore_configs, &mut self.spores);
-            self.tick += 1;
+            self.world.step();
+            self.record();
+        }
+        Ok(())
+    }
+
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        self.finish_recording();
+        Ok(false)
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        x: f32,
+        y: f32,
+    ) -> GameResult {
+        if button == MouseButton::Left {
+            let spores = self
+                .world
+                .spores()
+                .map(|spore| (spore.id, spore.position, spore.spore_type));
+            let radii = &self.world.spore_configs().radii;
+            self.selected = spore_at(&self.view, radii, x, y, spores);
         }
         Ok(())
     }
@@ -65,162 +158,451 @@ impl event::EventHandler<ggez::GameError> for Simulation {
             KeyCode::Space => {
                 self.paused = !self.paused;
             }
+            KeyCode::S => {
+                self.save_snapshot();
+            }
+            KeyCode::L => {
+                self.load_snapshot(ctx)?;
+            }
+            keycode => self.view.handle_key(keycode),
+        }
+        Result::Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        if self.world.tick().is_multiple_of(8) {
+            return Ok(());
+        }
+
+        let _background_color = rgb(34, 49, 63);
+
+        // graphics::clear(ctx, background_color);
+
+        let mut canvas =
+            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.1, 0.2, 0.3, 1.0]));
+        if let Some(mesh) = &self.obstacles_mesh {
+            canvas.draw(mesh, universe_param(&self.view));
+        }
+        draw_bonds(ctx, &mut canvas, &self.view, self.world.bonds())?;
+        draw_spores(
+            ctx,
+            &mut canvas,
+            &self.view,
+            &self.palette,
+            &self.world.spore_configs().radii,
+            self.world
+                .spores()
+                .map(|spore| (spore.position, spore.spore_type)),
+        )?;
+        if let Some(id) = self.selected {
+            let spore = self.world.spores().find(|spore| spore.id == id);
+            draw_selection(
+                ctx,
+                &mut canvas,
+                &self.view,
+                &self.world.spore_configs().radii,
+                id,
+                spore.map(|spore| (spore.position, spore.spore_type)),
+            )?;
+        }
+        show_numbers(ctx, &mut canvas, &self.world, &self.view)?;
+
+        canvas.finish(ctx)?;
+
+        // graphics::present(ctx)?;
+        Ok(())
+    }
+}
+
+impl View {
+    pub fn new(
+        window_width: f32,
+        window_height: f32,
+        universe_width: f32,
+        universe_height: f32,
+    ) -> View {
+        View {
+            position: Point2 { x: 0.0, y: 0.0 },
+            zoom: 1.0,
+            window_width,
+            window_height,
+            universe_width,
+            universe_height,
+        }
+    }
+
+    fn for_world(world: &World) -> View {
+        let config = world.config();
+        View::new(
+            config.window_width,
+            config.window_height,
+            config.universe_width(),
+            config.universe_height(),
+        )
+    }
+
+    /// The point of the universe under a point of the window.
+    pub fn to_universe(&self, x: f32, y: f32) -> Vector {
+        Vector {
+            x: (x - self.position.x) / self.zoom,
+            y: (y - self.position.y) / self.zoom,
+        }
+    }
+
+    pub fn to_window(&self, position: Vector) -> Vec2 {
+        Vec2::new(
+            position.x * self.zoom + self.position.x,
+            position.y * self.zoom + self.position.y,
+        )
+    }
+
+    fn height_ratio(&self) -> f32 {
+        self.window_height / self.universe_height
+    }
+
+    fn width_ratio(&self) -> f32 {
+        self.window_width / self.universe_width
+    }
+
+    fn min_zoom(&self) -> f32 {
+        self.height_ratio() // 1.0 Ideally should be f32::min(height_ratio, width_ratio)
+    }
+
+    pub fn handle_key(&mut self, keycode: KeyCode) {
+        match keycode {
             KeyCode::Comma => {
                 //zoom in
                 self.zoom = f32::min(MAX_ZOOM, self.zoom * (1.0 + ZOOM_SPEED));
             }
             KeyCode::Period => {
                 // zoom out
-                self.zoom = f32::max(MIN_ZOOM, self.zoom * (1.0 - ZOOM_SPEED));
+                self.zoom = f32::max(self.min_zoom(), self.zoom * (1.0 - ZOOM_SPEED));
 
                 // replace within bounds
-                self.view_position.y = f32::max(
-                    self.view_position.y,
-                    -UNIVERSE_HEIGHT * (self.zoom - HEIGHT_RATIO),
+                self.position.y = f32::max(
+                    self.position.y,
+                    -self.universe_height * (self.zoom - self.height_ratio()),
                 )
                 .round();
-                self.view_position.x = f32::max(
-                    self.view_position.x,
-                    -UNIVERSE_WIDTH * (self.zoom - WIDTH_RATIO),
+                self.position.x = f32::max(
+                    self.position.x,
+                    -self.universe_width * (self.zoom - self.width_ratio()),
                 )
                 .round();
             }
             KeyCode::Up => {
                 let min_allowable_position_y = 0.0;
-                self.view_position.y = f32::min(
-                    min_allowable_position_y,
-                    self.view_position.y + MOVE_INCREMENT,
-                )
-                .round();
+                self.position.y =
+                    f32::min(min_allowable_position_y, self.position.y + MOVE_INCREMENT).round();
             }
             KeyCode::Down => {
                 // within bounds
-                self.view_position.y = f32::max(
-                    self.view_position.y - MOVE_INCREMENT,
-                    -UNIVERSE_HEIGHT * (self.zoom - HEIGHT_RATIO),
+                self.position.y = f32::max(
+                    self.position.y - MOVE_INCREMENT,
+                    -self.universe_height * (self.zoom - self.height_ratio()),
                 )
                 .round();
             }
             KeyCode::Right => {
                 // within bounds
-                self.view_position.x = f32::max(
-                    self.view_position.x - MOVE_INCREMENT,
-                    -UNIVERSE_WIDTH * (self.zoom - WIDTH_RATIO),
+                self.position.x = f32::max(
+                    self.position.x - MOVE_INCREMENT,
+                    -self.universe_width * (self.zoom - self.width_ratio()),
                 )
                 .round();
             }
             KeyCode::Left => {
                 let min_allowable_position_x = 0.0;
-                self.view_position.x = f32::min(
-                    min_allowable_position_x,
-                    self.view_position.x + MOVE_INCREMENT,
-                )
-                .round();
+                self.position.x =
+                    f32::min(min_allowable_position_x, self.position.x + MOVE_INCREMENT).round();
             }
             _ => {}
         }
-        Result::Ok(())
     }
+}
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        if self.tick % 8 == 0 {
-            return Ok(());
-        }
-
-        let _background_color = rgb(34, 49, 63);
-
-        // graphics::clear(ctx, background_color);
-
-        let mut canvas =
-            graphics::Canvas::from_frame(ctx, graphics::Color::from([0.1, 0.2, 0.3, 1.0]));
-        draw_spores(ctx, &mut canvas, &self)?;
-        show_numbers(
-            ctx,
-            &mut canvas,
-            self.nr_of_spores,
-            self.tick,
-            self.zoom,
-            self.view_position,
+pub fn draw_spores(
+    ctx: &mut Context,
+    canvas: &mut graphics::Canvas,
+    view: &View,
+    palette: &[Color],
+    radii: &[f32],
+    spores: impl Iterator<Item = (Vector, u8)>,
+) -> GameResult {
+    let mut mesh_builder = graphics::MeshBuilder::new();
+    for (position, spore_type) in spores {
+        mesh_builder.circle(
+            graphics::DrawMode::fill(),
+            Vec2::new(position.x, position.y),
+            drawn_radius(radii, spore_type),
+            0.01,
+            palette[spore_type as usize],
         )?;
+    }
 
-        canvas.finish(ctx)?;
+    let mesh = mesh_builder.build();
+    canvas.draw(&Mesh::from_data(&ctx.gfx, mesh), universe_param(view));
 
-        // graphics::present(ctx)?;
-        Ok(())
-    }
+    Ok(())
 }
 
-fn draw_spores(
+/// A line per bond, under the spores. The bonds across a periodic edge are left out.
+pub fn draw_bonds(
     ctx: &mut Context,
     canvas: &mut graphics::Canvas,
-    universe: &Simulation,
+    view: &View,
+    bonds: impl Iterator<Item = (Vector, Vector)>,
 ) -> GameResult {
+    let color = rgb(236, 240, 241);
     let mut mesh_builder = graphics::MeshBuilder::new();
-    for (horz, vert) in get_buckets() {
-        let positions = &universe.spores.positions[vert][horz];
-        let spore_types = &universe.spores.spore_types[vert][horz];
-        for index in 0..(positions.len()) as usize {
-            mesh_builder.circle(
-                graphics::DrawMode::fill(),
-                Vec2::new(positions[index].x, positions[index].y),
-                4.0,
-                0.01,
-                get_color(spore_types[index]),
-            )?;
+    for (from, to) in bonds {
+        let wraps = (to.x - from.x).abs() > view.universe_width / 2.0
+            || (to.y - from.y).abs() > view.universe_height / 2.0;
+        // a line needs two distinct points
+        if wraps || from == to {
+            continue;
         }
+        mesh_builder.line(
+            &[Vec2::new(from.x, from.y), Vec2::new(to.x, to.y)],
+            1.0,
+            color,
+        )?;
     }
+    let mesh = mesh_builder.build();
+    if !mesh.vertices.is_empty() {
+        canvas.draw(&Mesh::from_data(&ctx.gfx, mesh), universe_param(view));
+    }
+    Ok(())
+}
 
+// draws a mesh in the coordinates of the universe
+fn universe_param(view: &View) -> graphics::DrawParam {
+    graphics::DrawParam::new()
+        .scale(Vector2 {
+            x: view.zoom,
+            y: view.zoom,
+        })
+        .dest(view.position)
+}
+
+/// All obstacles in a single mesh, in the coordinates of the universe. `None` without obstacles.
+pub fn obstacles_mesh(ctx: &Context, obstacles: &Obstacles) -> GameResult<Option<Mesh>> {
+    if obstacles.is_empty() {
+        return Ok(None);
+    }
+    let color = rgb(149, 165, 166);
+    let mut mesh_builder = graphics::MeshBuilder::new();
+    for obstacle in &obstacles.obstacles {
+        match obstacle {
+            Obstacle::Circle { center, radius } => {
+                mesh_builder.circle(
+                    graphics::DrawMode::fill(),
+                    Vec2::new(center.x, center.y),
+                    *radius,
+                    0.5,
+                    color,
+                )?;
+            }
+            Obstacle::Rectangle { min, max } => {
+                mesh_builder.rectangle(
+                    graphics::DrawMode::fill(),
+                    graphics::Rect::new(min.x, min.y, max.x - min.x, max.y - min.y),
+                    color,
+                )?;
+            }
+            Obstacle::Segment {
+                from,
+                to,
+                thickness,
+            } => {
+                mesh_builder.line(
+                    &[Vec2::new(from.x, from.y), Vec2::new(to.x, to.y)],
+                    *thickness,
+                    color,
+                )?;
+            }
+            Obstacle::Mask(mask) => {
+                // a rectangle per run of solid pixels in a row
+                for y in 0..mask.height {
+                    let row = &mask.solid[y * mask.width..(y + 1) * mask.width];
+                    let mut x = 0;
+                    while x < mask.width {
+                        if !row[x] {
+                            x += 1;
+                            continue;
+                        }
+                        let start = x;
+                        while x < mask.width && row[x] {
+                            x += 1;
+                        }
+                        mesh_builder.rectangle(
+                            graphics::DrawMode::fill(),
+                            graphics::Rect::new(
+                                start as f32 * mask.pixel_width,
+                                y as f32 * mask.pixel_height,
+                                (x - start) as f32 * mask.pixel_width,
+                                mask.pixel_height,
+                            ),
+                            color,
+                        )?;
+                    }
+                }
+            }
+        }
+    }
+    // a mask can be without solid pixels
     let mesh = mesh_builder.build();
+    if mesh.vertices.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Mesh::from_data(&ctx.gfx, mesh)))
+}
+
+// the radius of the point spores, with a radius of 0.0
+const SPORE_RADIUS: f32 = 4.0;
+
+fn drawn_radius(radii: &[f32], spore_type: u8) -> f32 {
+    match radii.get(spore_type as usize) {
+        Some(radius) if *radius > 0.0 => *radius,
+        _ => SPORE_RADIUS,
+    }
+}
+// how far from a spore a click still selects it, in pixels of the window
+const SELECT_DISTANCE: f32 = 8.0;
+
+/// The id of the spore under a click in the window, if there is one: the one with the nearest edge.
+pub fn spore_at(
+    view: &View,
+    radii: &[f32],
+    x: f32,
+    y: f32,
+    spores: impl Iterator<Item = (u32, Vector, u8)>,
+) -> Option<u32> {
+    let click = view.to_universe(x, y);
+    let max_dist = SELECT_DISTANCE / view.zoom;
+    spores
+        .map(|(id, position, spore_type)| {
+            let dist = (position.x - click.x).hypot(position.y - click.y);
+            (id, dist - drawn_radius(radii, spore_type))
+        })
+        .filter(|(_, dist)| *dist <= max_dist)
+        .min_by(|(_, dist), (_, other_dist)| dist.total_cmp(other_dist))
+        .map(|(id, _)| id)
+}
+
+/// Circles the selected spore and writes its id next to it.
+/// `spore` is `None` once the spore is gone.
+pub fn draw_selection(
+    ctx: &mut Context,
+    canvas: &mut graphics::Canvas,
+    view: &View,
+    radii: &[f32],
+    id: u32,
+    spore: Option<(Vector, u8)>,
+) -> GameResult {
+    let (position, spore_type) = match spore {
+        Some(spore) => spore,
+        None => return Ok(()),
+    };
+    let center = view.to_window(position);
+    let radius = drawn_radius(radii, spore_type) * view.zoom + 4.0;
+    canvas.draw(
+        &Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            center,
+            radius,
+            0.1,
+            Color::WHITE,
+        )?,
+        graphics::DrawParam::new(),
+    );
     canvas.draw(
-        &Mesh::from_data(&ctx.gfx, mesh),
-        graphics::DrawParam::new()
-            .scale(Vector2 {
-                x: universe.zoom,
-                y: universe.zoom,
-            })
-            .dest(universe.view_position),
+        graphics::Text::new(format!("id {} (type {})", id, spore_type))
+            .set_font("DejaVu")
+            .set_scale(24.0),
+        center + Vec2::new(radius + 4.0, -12.0),
     );
-
     Ok(())
 }
 
-fn get_color(spore_type: u8) -> Color {
-    match spore_type {
-        0 => rgb(238, 96, 85),  //red
-        1 => rgb(220, 130, 27), // green
-        2 => rgb(230, 210, 31), // light green
-        3 => rgb(131, 221, 27), // orange
-        4 => rgb(37, 186, 94),  // salmon
-        5 => rgb(29, 227, 234), // blue
-        6 => rgb(35, 96, 251),  // blue
-        7 => rgb(169, 40, 243), // blue
-        8 => rgb(230, 37, 237), // blue
-        _ => panic!(),
+/// One color per spore type: the original nine colors, or generated ones when there are more types.
+pub fn palette(number_of_configs: usize) -> Vec<Color> {
+    if number_of_configs <= ORIGINAL_COLORS.len() {
+        return ORIGINAL_COLORS
+            .iter()
+            .map(|&(r, g, b)| rgb(r, g, b))
+            .collect();
     }
+
+    (0..number_of_configs)
+        .map(|spore_type| {
+            // the golden angle keeps neighboring types apart, whatever the number of types
+            let hue = (spore_type as f32 * GOLDEN_ANGLE) % 360.0;
+            let saturation = [0.85, 0.6, 0.95][spore_type % 3];
+            let value = [0.95, 0.8, 0.65, 0.9][spore_type % 4];
+            hsv(hue, saturation, value)
+        })
+        .collect()
+}
+
+const GOLDEN_ANGLE: f32 = 137.507_77;
+
+const ORIGINAL_COLORS: [(u8, u8, u8); 9] = [
+    (238, 96, 85),  // red
+    (220, 130, 27), // orange
+    (230, 210, 31), // yellow
+    (131, 221, 27), // light green
+    (37, 186, 94),  // green
+    (29, 227, 234), // cyan
+    (35, 96, 251),  // blue
+    (169, 40, 243), // purple
+    (230, 37, 237), // pink
+];
+
+fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    Color::new(r + m, g + m, b + m, 1.0)
 }
 
 fn show_numbers(
     ctx: &mut Context,
     canvas: &mut graphics::Canvas,
-    nr_of_spores: u16,
-    tick: u32,
-    zoom: f32,
-    position: Point2<f32>,
+    world: &World,
+    view: &View,
 ) -> GameResult {
+    let tick = world.tick();
+    let reactions = if world.config().reactions.is_empty() {
+        String::new()
+    } else {
+        format!("\nReactions: {:?}", world.reaction_counts())
+    };
     // Text is drawn from the top-left corner.
     let offset = 10.0;
     let dest_point = ggez::glam::Vec2::new(offset, offset);
     canvas.draw(
         graphics::Text::new(
             format!(
-                "#spores: {}\nTime: {}\nFPS: {:.2}\nTick: {}\nAVG ticks/s: {:.2}\nZoom: x{:.2}\nCoords: {:?}",
-                nr_of_spores,
+                "#spores: {}\nTime: {}\nFPS: {:.2}\nTick: {}\nAVG ticks/s: {:.2}\nTemperature: {:.4}\nZoom: x{:.2}\nCoords: {:?}{}",
+                world.nr_of_spores(),
                 format_duration(ctx.time.time_since_start().as_secs()),
                 ctx.time.fps(),
                 tick,
                 (tick as f32) / ctx.time.time_since_start().as_secs_f32(),
-                zoom,
-                position,
+                world.kinetic_temperature(),
+                view.zoom,
+                view.position,
+                reactions,
             ),
         )
             .set_font("DejaVu")
@@ -231,11 +613,11 @@ fn show_numbers(
     Ok(())
 }
 
-fn format_duration(secs: u64) -> String {
+pub fn format_duration(secs: u64) -> String {
     format!("{}m{}s", secs / 60, secs % 60)
 }
 
-fn rgb(r: u8, g: u8, b: u8) -> Color {
+pub fn rgb(r: u8, g: u8, b: u8) -> Color {
     [
         (r as f32) / 255.0,
         (g as f32) / 255.0,
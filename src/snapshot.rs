@@ -0,0 +1,160 @@
+use crate::{
+    bucket::Grid,
+    configuration::SimulationConfig,
+    spore::{SporeConfigs, SporesState},
+    world::World,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+// A snapshot file is the magic bytes, the format version (u32, little endian) and then the bincode
+// encoded (config, spore configs, spores, tick). The seed is stored in the config.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"PYCS";
+pub const SNAPSHOT_VERSION: u32 = 16;
+
+pub fn save_snapshot(world: &World, path: &Path) -> Result<(), SnapshotError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(SNAPSHOT_MAGIC)?;
+    writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+    bincode::serialize_into(
+        &mut writer,
+        &(
+            world.config(),
+            world.spore_configs(),
+            world.state(),
+            world.tick(),
+        ),
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_snapshot(path: &Path) -> Result<World, SnapshotError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let (config, spore_configs, spores, tick): (SimulationConfig, SporeConfigs, SporesState, u32) =
+        bincode::deserialize_from(&mut reader)?;
+    config
+        .validate()
+        .map_err(|err| SnapshotError::Invalid(err.to_string()))?;
+    let number_of_configs = config.number_of_configs;
+    check_spore_configs(&config, &spore_configs).map_err(|message| {
+        SnapshotError::Invalid(format!("the spore configurations {}", message))
+    })?;
+    if spores
+        .spore_types
+        .iter()
+        .any(|spore_type| *spore_type as usize >= number_of_configs)
+    {
+        return Err(SnapshotError::Invalid(
+            "a spore has a type beyond number_of_configs".to_string(),
+        ));
+    }
+    if spores.grid != Grid::new(&config, &spore_configs) || !spores.is_consistent() {
+        return Err(SnapshotError::Invalid(
+            "the buckets don't match the configuration".to_string(),
+        ));
+    }
+
+    Ok(World::from_parts(config, spore_configs, spores, tick))
+}
+
+// the spore configurations are stored as they were used, whether generated, pasted or built by hand
+fn check_spore_configs(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+) -> Result<(), &'static str> {
+    let number_of_configs = config.number_of_configs;
+    if spore_configs.number_of_configs() != number_of_configs
+        || spore_configs.force_factors.size() != number_of_configs
+        || spore_configs.force_reaches.size() != number_of_configs
+        || spore_configs.masses.len() != number_of_configs
+        || spore_configs.radii.len() != number_of_configs
+    {
+        return Err("need a value per spore type");
+    }
+    if !spore_configs
+        .force_reaches
+        .rows()
+        .flatten()
+        .all(|reach| *reach > 0.0 && *reach <= config.max_force_reach)
+    {
+        return Err("need force reaches between 0.0 and max_force_reach");
+    }
+    if !spore_configs
+        .repulsion_dists
+        .rows()
+        .chain(spore_configs.force_factors.rows())
+        .flatten()
+        .all(|value| value.is_finite())
+    {
+        return Err("need finite repulsion distances and force factors");
+    }
+    if !(spore_configs
+        .masses
+        .iter()
+        .all(|mass| mass.is_finite() && *mass > 0.0)
+        && spore_configs
+            .radii
+            .iter()
+            .all(|radius| (0.0..f32::INFINITY).contains(radius)))
+    {
+        return Err("need finite positive masses and finite radii of at least 0.0");
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(err: bincode::Error) -> Self {
+        SnapshotError::Encoding(err)
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "cannot access snapshot file: {}", err),
+            SnapshotError::Encoding(err) => write!(f, "cannot encode or decode snapshot: {}", err),
+            SnapshotError::NotASnapshot => write!(f, "not a pycniospores snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} is not supported, expected version {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Invalid(message) => write!(f, "invalid snapshot: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
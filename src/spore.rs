@@ -1,17 +1,394 @@
-use crate::{configuration::NUMBER_OF_CONFIGS, vector::Vector};
-
-type Buckets<T> = Vec<Vec<T>>;
+use crate::{
+    bond::Bond, bucket::Grid, field::Fields, obstacle::Obstacles, reaction::Contact, vector::Vector,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Index, IndexMut, Range},
+    sync::Arc,
+};
 
+/// All spores, stored flat per field and sorted by bucket (see `Grid::bucket_index`):
+/// the spores of bucket `b` are at `cell_starts[b]..cell_starts[b + 1]`.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SporesState {
-    pub positions: Buckets<Vec<Vector>>,
-    pub speeds: Buckets<Vec<Vector>>,
-    pub spore_types: Buckets<Vec<u8>>,
+    pub positions: Vec<Vector>,
+    pub speeds: Vec<Vector>,
+    pub spore_types: Vec<u8>,
+    // a spore keeps its id for its whole life, ids are never reused
+    pub ids: Vec<u32>,
+    pub next_id: u32,
+    pub grid: Grid,
+    pub cell_starts: Vec<usize>,
+    // never change during a run, shared with the copies of the state
+    pub obstacles: Arc<Obstacles>,
+    pub fields: Arc<Fields>,
+    // the number of ticks times dt, for the fields that change over time
+    pub time: f64,
+    // by id, so the bonds don't change when the spores are sorted
+    pub bonds: Vec<Bond>,
+    // the pairs of spores within reach of a reaction, and the reactions per rule in the last tick
+    pub contacts: Vec<Contact>,
+    pub reaction_counts: Vec<u32>,
+}
+
+impl SporesState {
+    /// Numbers the given spores from 0, in the given order, and sorts them into their buckets.
+    /// The time starts at 0.0, without bonds or contacts.
+    pub fn from_spores(
+        grid: Grid,
+        obstacles: Arc<Obstacles>,
+        fields: Arc<Fields>,
+        positions: Vec<Vector>,
+        speeds: Vec<Vector>,
+        spore_types: Vec<u8>,
+    ) -> SporesState {
+        let nr_of_spores = positions.len() as u32;
+        let mut spores = SporesState {
+            positions,
+            speeds,
+            spore_types,
+            ids: (0..nr_of_spores).collect(),
+            next_id: nr_of_spores,
+            grid,
+            cell_starts: Vec::new(),
+            obstacles,
+            fields,
+            time: 0.0,
+            bonds: Vec::new(),
+            contacts: Vec::new(),
+            reaction_counts: Vec::new(),
+        };
+        spores.sort_into_buckets();
+        spores
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn bucket_range(&self, bucket_index: usize) -> Range<usize> {
+        self.cell_starts[bucket_index]..self.cell_starts[bucket_index + 1]
+    }
+
+    /// The index of every id, `usize::MAX` for the ids of spores that are gone.
+    pub fn indexes_by_id(&self) -> Vec<usize> {
+        let mut indexes = vec![usize::MAX; self.next_id as usize];
+        for (index, id) in self.ids.iter().enumerate() {
+            indexes[*id as usize] = index;
+        }
+        indexes
+    }
+
+    /// Adds a spore with a new id, call `sort_into_buckets` afterwards.
+    pub(crate) fn push(&mut self, position: Vector, speed: Vector, spore_type: u8) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.positions.push(position);
+        self.speeds.push(speed);
+        self.spore_types.push(spore_type);
+        self.ids.push(id);
+        id
+    }
+
+    /// Keeps the spores whose position satisfies `keep`, see `retain_indexes`.
+    pub(crate) fn retain(&mut self, keep: impl Fn(Vector) -> bool) {
+        let kept: Vec<usize> = (0..self.len())
+            .filter(|index| keep(self.positions[*index]))
+            .collect();
+        self.keep_spores(&kept);
+    }
+
+    /// Keeps the spores whose index satisfies `keep` with their bonds and contacts,
+    /// call `sort_into_buckets` afterwards.
+    pub(crate) fn retain_indexes(&mut self, keep: impl Fn(usize) -> bool) {
+        let kept: Vec<usize> = (0..self.len()).filter(|index| keep(*index)).collect();
+        self.keep_spores(&kept);
+    }
+
+    fn keep_spores(&mut self, kept: &[usize]) {
+        self.positions = kept.iter().map(|&i| self.positions[i]).collect();
+        self.speeds = kept.iter().map(|&i| self.speeds[i]).collect();
+        self.spore_types = kept.iter().map(|&i| self.spore_types[i]).collect();
+        self.ids = kept.iter().map(|&i| self.ids[i]).collect();
+        let indexes = self.indexes_by_id();
+        let exists = |id: &u32| indexes[*id as usize] != usize::MAX;
+        self.bonds.retain(|bond| bond.ids.iter().all(exists));
+        self.contacts
+            .retain(|contact| contact.ids.iter().all(exists));
+    }
+
+    /// Counting sort on the bucket of every position, the spores keep their order within a bucket.
+    pub fn sort_into_buckets(&mut self) {
+        let grid = &self.grid;
+        let buckets: Vec<usize> = self
+            .positions
+            .par_iter()
+            .map(|position| grid.bucket_index(grid.get_bucket_from_pos(*position)))
+            .collect();
+
+        // the number of spores per bucket, then the prefix sum of those counts
+        let mut cell_starts = vec![0; grid.nr_buckets() + 1];
+        for bucket in &buckets {
+            cell_starts[*bucket] += 1;
+        }
+        let mut total = 0;
+        for cell_start in cell_starts.iter_mut() {
+            let count = *cell_start;
+            *cell_start = total;
+            total += count;
+        }
+
+        let mut next_indexes = cell_starts.clone();
+        let mut old_indexes = vec![0; buckets.len()];
+        for (old_index, bucket) in buckets.iter().enumerate() {
+            old_indexes[next_indexes[*bucket]] = old_index;
+            next_indexes[*bucket] += 1;
+        }
+
+        self.positions = old_indexes.iter().map(|&i| self.positions[i]).collect();
+        self.speeds = old_indexes.iter().map(|&i| self.speeds[i]).collect();
+        self.spore_types = old_indexes.iter().map(|&i| self.spore_types[i]).collect();
+        self.ids = old_indexes.iter().map(|&i| self.ids[i]).collect();
+        self.cell_starts = cell_starts;
+    }
+
+    /// Whether the fields have the same length, the bonds and contacts are between existing spores
+    /// and the spores are sorted into the buckets of the grid.
+    pub fn is_consistent(&self) -> bool {
+        let grid = &self.grid;
+        self.speeds.len() == self.len()
+            && self.spore_types.len() == self.len()
+            && self.ids.len() == self.len()
+            && self.ids.iter().all(|id| *id < self.next_id)
+            && self
+                .bonds
+                .iter()
+                .all(|bond| bond.ids[0] < bond.ids[1] && bond.ids[1] < self.next_id)
+            && {
+                let indexes = self.indexes_by_id();
+                let exists = |id: &u32| *id < self.next_id && indexes[*id as usize] != usize::MAX;
+                self.bonds.iter().all(|bond| bond.ids.iter().all(exists))
+                    && self
+                        .contacts
+                        .iter()
+                        .all(|contact| contact.ids.iter().all(exists))
+            }
+            && self.obstacles.is_indexed_for(grid)
+            && self.cell_starts.len() == grid.nr_buckets() + 1
+            && self.cell_starts.first() == Some(&0)
+            && self.cell_starts.last() == Some(&self.len())
+            && (0..grid.nr_buckets()).all(|bucket| {
+                self.cell_starts[bucket] <= self.cell_starts[bucket + 1]
+                    && self.positions[self.bucket_range(bucket)]
+                        .iter()
+                        .all(|position| {
+                            grid.bucket_index(grid.get_bucket_from_pos(*position)) == bucket
+                        })
+            })
+    }
+}
+
+/// A square matrix with a value per (own type, other type), indexed as `matrix[(own_type, other_type)]`.
+// Stored flat: one level of redirection less than a Vec of Vecs, and a row stays in the CPU cache.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "Vec<Vec<f32>>", try_from = "Vec<Vec<f32>>")]
+pub struct InteractionMatrix {
+    size: usize,
+    values: Vec<f32>,
+}
+
+impl InteractionMatrix {
+    pub fn new(size: usize) -> InteractionMatrix {
+        InteractionMatrix {
+            size,
+            values: vec![0.0; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The values for all other types.
+    #[inline]
+    pub fn row(&self, own_type: usize) -> &[f32] {
+        &self.values[own_type * self.size..(own_type + 1) * self.size]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        self.values.chunks(self.size.max(1))
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        (0..self.size).all(|own_type| {
+            (own_type + 1..self.size)
+                .all(|other_type| self[(own_type, other_type)] == self[(other_type, own_type)])
+        })
+    }
+}
+
+impl Index<(usize, usize)> for InteractionMatrix {
+    type Output = f32;
+
+    #[inline]
+    fn index(&self, (own_type, other_type): (usize, usize)) -> &f32 {
+        &self.values[own_type * self.size + other_type]
+    }
+}
+
+impl IndexMut<(usize, usize)> for InteractionMatrix {
+    #[inline]
+    fn index_mut(&mut self, (own_type, other_type): (usize, usize)) -> &mut f32 {
+        &mut self.values[own_type * self.size + other_type]
+    }
 }
 
-// Uses Arrays instead of Vectors. Arrays are probably loaded to the CPU cache, while Vector has one more level of redirection.
-#[derive(Debug)]
+impl From<InteractionMatrix> for Vec<Vec<f32>> {
+    fn from(matrix: InteractionMatrix) -> Self {
+        matrix.rows().map(<[f32]>::to_vec).collect()
+    }
+}
+
+impl TryFrom<Vec<Vec<f32>>> for InteractionMatrix {
+    type Error = String;
+
+    fn try_from(rows: Vec<Vec<f32>>) -> Result<Self, Self::Error> {
+        let size = rows.len();
+        if rows.iter().any(|row| row.len() != size) {
+            return Err(format!(
+                "an interaction matrix needs {} rows of {} values",
+                size, size
+            ));
+        }
+        Ok(InteractionMatrix {
+            size,
+            values: rows.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// How every spore type reacts to every other type: `force_factors[(own_type, other_type)]`,
+/// and the mass and radius of every type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SporeConfigs {
-    pub repulsion_dists: [f32; NUMBER_OF_CONFIGS],
-    pub force_factors: [f32; NUMBER_OF_CONFIGS],
-    pub force_reaches: [f32; NUMBER_OF_CONFIGS],
+    pub repulsion_dists: InteractionMatrix,
+    pub force_factors: InteractionMatrix,
+    pub force_reaches: InteractionMatrix,
+    // the forces on a spore are divided by the mass of its type
+    #[serde(default)]
+    pub masses: Vec<f32>,
+    // a spore is drawn with the radius of its type, see `apply_radii` for the repulsion
+    #[serde(default)]
+    pub radii: Vec<f32>,
+}
+
+impl SporeConfigs {
+    /// All types have a mass of 1.0 and are points, with a radius of 0.0.
+    pub fn new(number_of_configs: usize) -> SporeConfigs {
+        SporeConfigs {
+            repulsion_dists: InteractionMatrix::new(number_of_configs),
+            force_factors: InteractionMatrix::new(number_of_configs),
+            force_reaches: InteractionMatrix::new(number_of_configs),
+            masses: vec![1.0; number_of_configs],
+            radii: vec![0.0; number_of_configs],
+        }
+    }
+
+    /// Every type reacts identically to a given other type: all rows of the matrices are the same.
+    pub fn by_other_type(
+        repulsion_dists: &[f32],
+        force_factors: &[f32],
+        force_reaches: &[f32],
+    ) -> SporeConfigs {
+        let number_of_configs = repulsion_dists.len();
+        assert!(force_factors.len() == number_of_configs);
+        assert!(force_reaches.len() == number_of_configs);
+
+        let mut spore_configs = SporeConfigs::new(number_of_configs);
+        for own_type in 0..number_of_configs {
+            for other_type in 0..number_of_configs {
+                let index = (own_type, other_type);
+                spore_configs.repulsion_dists[index] = repulsion_dists[other_type];
+                spore_configs.force_factors[index] = force_factors[other_type];
+                spore_configs.force_reaches[index] = force_reaches[other_type];
+            }
+        }
+        spore_configs
+    }
+
+    pub fn number_of_configs(&self) -> usize {
+        self.repulsion_dists.size()
+    }
+
+    /// Two spores repel each other at least until they touch: raises the repulsion distances
+    /// to the sum of the radii of the two types.
+    pub fn apply_radii(&mut self) {
+        for own_type in 0..self.number_of_configs() {
+            for other_type in 0..self.number_of_configs() {
+                let contact_dist = self.radii[own_type] + self.radii[other_type];
+                let repulsion_dist = &mut self.repulsion_dists[(own_type, other_type)];
+                *repulsion_dist = repulsion_dist.max(contact_dist);
+            }
+        }
+    }
+
+    /// Whether two spores push or pull each other alike, whatever their types: the forces between
+    /// two spores are then equal and opposite (Newton's third law).
+    pub fn is_symmetric(&self) -> bool {
+        self.repulsion_dists.is_symmetric()
+            && self.force_factors.is_symmetric()
+            && self.force_reaches.is_symmetric()
+    }
+}
+
+// prints the configs as a TOML table, so they can be pasted in a configuration file
+impl fmt::Display for SporeConfigs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(2);
+        let matrices = [
+            ("repulsion_dists", &self.repulsion_dists),
+            ("force_factors", &self.force_factors),
+            ("force_reaches", &self.force_reaches),
+        ];
+
+        write!(f, "[spore_configs]")?;
+        for (name, matrix) in matrices {
+            writeln!(f)?;
+            writeln!(f, "{} = [", name)?;
+            for row in matrix.rows() {
+                let row: Vec<String> = row
+                    .iter()
+                    .map(|value| format!("{:.*}", precision, value))
+                    .collect();
+                writeln!(f, "    [{}],", row.join(", "))?;
+            }
+            write!(f, "]")?;
+        }
+        for (name, values) in [("masses", &self.masses), ("radii", &self.radii)] {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| format!("{:.*}", precision, value))
+                .collect();
+            writeln!(f)?;
+            write!(f, "{} = [{}]", name, values.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A copy of the data of a single spore.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spore {
+    pub id: u32,
+    pub position: Vector,
+    pub speed: Vector,
+    pub spore_type: u8,
 }
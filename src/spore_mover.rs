@@ -1,106 +1,64 @@
 use crate::{
-    bucket::{get_bucket_from_pos, BucketCoord},
-    configuration::{NR_HORZ_BUCKETS, NR_VERT_BUCKETS},
-    movement_calculator::calc_new_positions_and_speeds,
+    bond::{bond_forces, update_bonds},
+    boundary::{is_absorbed, Boundary},
+    configuration::{SimulationConfig, UpdateMode},
+    movement_calculator::{calc_forces, calc_forces_with_bonds, update_spores_with_forces},
+    reaction::update_reactions,
     spore::{SporeConfigs, SporesState},
-    vector::Vector,
 };
-use std::usize;
 
 //  TWO loops
-//  1. calculate forces
-//  2. apply forces
-//      3. update speeds: apply forces + friction to speeds
-//      4. move according to speed
-pub fn move_spores(spore_configs: &SporeConfigs, spores_state: &mut SporesState) {
-    for vert in 0..NR_VERT_BUCKETS {
-        for horz in 0..NR_HORZ_BUCKETS {
-            move_spores_in_bucket(spore_configs, spores_state, (horz as usize, vert as usize));
+//  1. move the spores, see UpdateMode
+//      - calculate forces
+//      - apply forces: update speeds (forces + friction) and move according to speed
+//  2. remove the spores that left the universe across an absorbing edge
+//  3. sort the spores into the buckets of their new positions
+//  4. advance the time by dt
+//  5. break and form the bonds, which refer to the spores by id and so survive the sorting
+//  6. let the spores that have been in contact long enough react
+pub fn move_spores(
+    config: &SimulationConfig,
+    spore_configs: &SporeConfigs,
+    spores_state: &mut SporesState,
+) {
+    match config.update_mode {
+        UpdateMode::Synchronous if config.integrator.evaluates_forces_once() => {
+            // every force is calculated before any spore moves
+            let all_spores = 0..spores_state.len();
+            let forces = calc_forces(config, spore_configs, spores_state, all_spores.clone());
+            update_spores_with_forces(config, spore_configs, spores_state, all_spores, &forces);
         }
-    }
-
-    for vert in 0..NR_VERT_BUCKETS {
-        for horz in 0..NR_HORZ_BUCKETS {
-            let bucket_movements = calc_bucket_movements(&spores_state, horz, vert);
-            copy_spores_to_new_bucket(&bucket_movements, spores_state);
-            remove_spores_from_old_buckets(&bucket_movements, spores_state, vert, horz);
+        UpdateMode::Synchronous => {
+            config
+                .integrator
+                .step_whole_state(config, spore_configs, spores_state);
+        }
+        UpdateMode::Sequential => {
+            // the springs pull as at the start of the tick, the spores keep their indexes until sorted
+            let bond_forces = bond_forces(config, spores_state);
+            for bucket in 0..spores_state.grid.nr_buckets() {
+                let range = spores_state.bucket_range(bucket);
+                let forces = calc_forces_with_bonds(
+                    config,
+                    spore_configs,
+                    spores_state,
+                    range.clone(),
+                    &bond_forces,
+                );
+                update_spores_with_forces(config, spore_configs, spores_state, range, &forces);
+            }
         }
     }
-}
 
-fn move_spores_in_bucket(
-    spore_configs: &SporeConfigs,
-    spores: &mut SporesState,
-    (horz, vert): BucketCoord,
-) {
-    let (indexes, (new_poss, new_speeds)) =
-        calc_new_positions_and_speeds(spore_configs, spores, (horz, vert));
-
-    for spore in indexes {
-        spores.positions[vert][horz][spore] = new_poss[spore];
-        spores.speeds[vert][horz][spore] = new_speeds[spore];
+    if config.boundaries.has(Boundary::Absorbing) {
+        spores_state.retain(|position| !is_absorbed(config, position));
     }
-}
-
-fn copy_spores_to_new_bucket(
-    bucket_movements: &Vec<SporeBucketMovement>,
-    spores: &mut SporesState,
-) {
-    for movement in bucket_movements {
-        let (new_horz, new_vert) = movement.new_bucket_coord;
-        let (pos, speed, spore_type) = movement.spore_data;
-
-        spores.positions[new_vert][new_horz].push(pos);
-        spores.speeds[new_vert][new_horz].push(speed);
-        spores.spore_types[new_vert][new_horz].push(spore_type);
+    spores_state.sort_into_buckets();
+    spores_state.time += config.dt as f64;
+    if !config.bond_rules.is_empty() {
+        update_bonds(config, spores_state);
     }
-}
-
-/// remove spores that have moved (=only keep spores that haven't moved) (uses black magic :/)
-fn remove_spores_from_old_buckets(
-    bucket_movements: &Vec<SporeBucketMovement>,
-    spores: &mut SporesState,
-    vert: usize,
-    horz: usize,
-) {
-    let bucket_movement: Vec<usize> = bucket_movements
-        .iter()
-        .map(|movement| movement.index_in_old_bucket)
-        .collect();
-    let mut i: usize = 0;
-    spores.positions[vert][horz].retain(|_| (!bucket_movement.contains(&i), i += 1).0);
-    i = 0;
-    spores.speeds[vert][horz].retain(|_| (!bucket_movement.contains(&i), i += 1).0);
-    i = 0;
-    spores.spore_types[vert][horz].retain(|_| (!bucket_movement.contains(&i), i += 1).0);
-}
-
-struct SporeBucketMovement {
-    index_in_old_bucket: usize,
-    new_bucket_coord: BucketCoord,
-    spore_data: (Vector, Vector, u8),
-}
-
-fn calc_bucket_movements(
-    spores: &SporesState,
-    horz: usize,
-    vert: usize,
-) -> Vec<SporeBucketMovement> {
-    let mut bucket_movements = Vec::with_capacity(spores.positions[vert][horz].len());
-
-    for old_bucket_index in 0..spores.positions[vert][horz].len() {
-        let new_bucket_coord = get_bucket_from_pos(spores.positions[vert][horz][old_bucket_index]);
-        if new_bucket_coord != (horz, vert) {
-            bucket_movements.push(SporeBucketMovement {
-                index_in_old_bucket: old_bucket_index,
-                new_bucket_coord,
-                spore_data: (
-                    spores.positions[vert][horz][old_bucket_index],
-                    spores.speeds[vert][horz][old_bucket_index],
-                    spores.spore_types[vert][horz][old_bucket_index],
-                ),
-            });
-        }
+    if !config.reactions.is_empty() {
+        update_reactions(config, spores_state);
     }
-    bucket_movements
 }
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+use crate::{
+    configuration::SimulationConfig,
+    spore::{SporeConfigs, SporesState},
+    vector::{Vector, ZERO_VECTOR},
+};
+
+/// How the speeds of the spores are damped, and whether they are kicked around by thermal noise.
+/// The temperature is in units of kinetic energy, so heavier spores move slower at the same one.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Thermostat {
+    /// Only the `friction`, the spores come to rest in fixed clusters.
+    #[default]
+    Off,
+    /// Langevin dynamics: the speeds are damped at the rate `damping` instead of the `friction`,
+    /// and Gaussian noise keeps the spores at `temperature`.
+    Langevin {
+        temperature: f32,
+        damping: f32,
+        #[serde(default)]
+        annealing: Option<Annealing>,
+    },
+}
+
+/// Cools (or heats) the thermostat linearly from its temperature to `final_temperature`
+/// over a time of `duration`, then keeps it there.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Annealing {
+    pub final_temperature: f32,
+    pub duration: f32,
+}
+
+impl Thermostat {
+    pub fn is_valid(self) -> bool {
+        match self {
+            Thermostat::Off => true,
+            Thermostat::Langevin {
+                temperature,
+                damping,
+                annealing,
+            } => {
+                temperature >= 0.0
+                    && damping > 0.0
+                    && annealing.is_none_or(|annealing| {
+                        annealing.final_temperature >= 0.0 && annealing.duration > 0.0
+                    })
+            }
+        }
+    }
+
+    /// The temperature the thermostat keeps the spores at, at `time`.
+    pub fn temperature(self, time: f64) -> f32 {
+        match self {
+            Thermostat::Off => 0.0,
+            Thermostat::Langevin {
+                temperature,
+                annealing: None,
+                ..
+            } => temperature,
+            Thermostat::Langevin {
+                temperature,
+                annealing: Some(annealing),
+                ..
+            } => {
+                let progress = (time / annealing.duration as f64).min(1.0) as f32;
+                temperature + (annealing.final_temperature - temperature) * progress
+            }
+        }
+    }
+}
+
+/// The damping rate gamma of dv/dt = force - gamma * v.
+pub fn damping_rate(config: &SimulationConfig) -> f32 {
+    match config.thermostat {
+        Thermostat::Off => -config.friction.ln(),
+        Thermostat::Langevin { damping, .. } => damping,
+    }
+}
+
+/// The speed of spore `id` of `mass` after a tick of damping and thermal noise, starting at `time`:
+/// the exact solution of dv = -gamma * v * dt + sqrt(2 * gamma * temperature / mass) * dW.
+pub fn damp(config: &SimulationConfig, speed: Vector, id: u32, time: f64, mass: f32) -> Vector {
+    match config.thermostat {
+        // exact for the friction alone, and bit-identical to `speed * friction` at dt = 1.0
+        Thermostat::Off => speed * config.friction.powf(config.dt),
+        Thermostat::Langevin { damping, .. } => {
+            speed * (-damping * config.dt).exp() + thermal_noise(config, id, time, mass)
+        }
+    }
+}
+
+/// The random part of `damp`, on its own for the integrators that damp the speeds themselves.
+///
+/// The noise only depends on the seed, the id of the spore and the time, so it doesn't depend on
+/// the order in which the spores are updated and a run can be reproduced from its seed.
+pub fn thermal_noise(config: &SimulationConfig, id: u32, time: f64, mass: f32) -> Vector {
+    let (temperature, damping) = match config.thermostat {
+        Thermostat::Off => return ZERO_VECTOR,
+        Thermostat::Langevin { damping, .. } => (config.thermostat.temperature(time), damping),
+    };
+    // the variance of the speed relaxes to temperature / mass
+    let deviation = (temperature / mass * (1.0 - (-2.0 * damping * config.dt).exp())).sqrt();
+
+    // chained rather than xor-ed: seed 5 and spore 6 mustn't give the noise of seed 6 and spore 5
+    let mut state = split_mix(
+        split_mix(split_mix(config.seed.unwrap_or_default()) ^ id as u64) ^ time.to_bits(),
+    );
+    // Box-Muller: two uniform numbers in (0, 1] give two independent standard normal ones
+    let mut uniform = || {
+        state = split_mix(state);
+        to_unit(state)
+    };
+    let radius = (-2.0 * uniform().ln()).sqrt();
+    let angle = TAU * uniform();
+    Vector {
+        x: (radius * angle.cos()) as f32,
+        y: (radius * angle.sin()) as f32,
+    } * deviation
+}
+
+// SplitMix64: a well mixed hash of a counter
+pub(crate) fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// a uniform number in (0, 1] from the 53 highest bits of a hash
+pub(crate) fn to_unit(hash: u64) -> f64 {
+    ((hash >> 11) + 1) as f64 / (1u64 << 53) as f64
+}
+
+/// The temperature of the spores from their speeds: `<mass * |v|²> / 2` in two dimensions,
+/// so that every degree of freedom holds half of it as kinetic energy.
+pub fn kinetic_temperature(spore_configs: &SporeConfigs, spores: &SporesState) -> f32 {
+    if spores.is_empty() {
+        return 0.0;
+    }
+    let sum_of_energies: f64 = spores
+        .speeds
+        .iter()
+        .zip(&spores.spore_types)
+        .map(|(speed, spore_type)| {
+            let mass = spore_configs.masses[*spore_type as usize] as f64;
+            mass * ((speed.x as f64).powi(2) + (speed.y as f64).powi(2))
+        })
+        .sum();
+    (sum_of_energies / (2.0 * spores.len() as f64)) as f32
+}
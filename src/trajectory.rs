@@ -0,0 +1,384 @@
+use crate::{spore::SporeConfigs, world::World};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// A trajectory file is laid out as:
+//   magic "PYCT" | version: u32 | header length: u64 | bincode TrajectoryHeader
+//   per chunk:     compressed length: u32 | snappy compressed bincode Vec<Frame>
+//   at the end:    bincode Vec<ChunkEntry> | index offset: u64 | magic "PYCI"
+// All integers are little endian. The index at the end makes the file seekable, when it's missing
+// (the recording was interrupted) the chunks are scanned instead.
+const TRAJECTORY_MAGIC: &[u8; 4] = b"PYCT";
+const INDEX_MAGIC: &[u8; 4] = b"PYCI";
+pub const TRAJECTORY_VERSION: u32 = 5;
+
+const FRAMES_PER_CHUNK: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrajectoryHeader {
+    pub spore_configs: SporeConfigs,
+    pub universe_width: f32,
+    pub universe_height: f32,
+    pub seed: Option<u64>,
+    pub record_interval: u32,
+}
+
+/// The spores at one tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub tick: u32,
+    pub spores: Vec<RecordedSpore>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct RecordedSpore {
+    /// The id of the spore, the same in every frame.
+    pub id: u32,
+    pub spore_type: u8,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+struct ChunkEntry {
+    offset: u64,
+    first_frame: usize,
+    first_tick: u32,
+    nr_frames: usize,
+}
+
+/// Writes the spores every `record_interval` ticks, in compressed chunks of frames.
+pub struct TrajectoryRecorder {
+    writer: BufWriter<File>,
+    offset: u64,
+    record_interval: u32,
+    nr_frames: usize,
+    chunk: Vec<Frame>,
+    index: Vec<ChunkEntry>,
+}
+
+impl TrajectoryRecorder {
+    /// Writes the header and records the current tick of the world as first frame.
+    pub fn create(
+        path: &Path,
+        world: &World,
+        record_interval: u32,
+    ) -> Result<TrajectoryRecorder, TrajectoryError> {
+        assert!(record_interval > 0, "the record interval must be positive");
+        let header = TrajectoryHeader {
+            spore_configs: world.spore_configs().clone(),
+            universe_width: world.config().universe_width(),
+            universe_height: world.config().universe_height(),
+            seed: world.seed(),
+            record_interval,
+        };
+        let header = bincode::serialize(&header)?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(TRAJECTORY_MAGIC)?;
+        writer.write_all(&TRAJECTORY_VERSION.to_le_bytes())?;
+        writer.write_all(&(header.len() as u64).to_le_bytes())?;
+        writer.write_all(&header)?;
+
+        let mut recorder = TrajectoryRecorder {
+            writer,
+            offset: (TRAJECTORY_MAGIC.len() + 4 + 8 + header.len()) as u64,
+            record_interval,
+            nr_frames: 0,
+            chunk: Vec::with_capacity(FRAMES_PER_CHUNK),
+            index: Vec::new(),
+        };
+        recorder.record_frame(world)?;
+        Ok(recorder)
+    }
+
+    /// Call after every step: only records the ticks that are a multiple of the record interval.
+    pub fn record(&mut self, world: &World) -> Result<(), TrajectoryError> {
+        if world.tick().is_multiple_of(self.record_interval) {
+            self.record_frame(world)?;
+        }
+        Ok(())
+    }
+
+    fn record_frame(&mut self, world: &World) -> Result<(), TrajectoryError> {
+        let spores = world
+            .spores()
+            .map(|spore| RecordedSpore {
+                id: spore.id,
+                spore_type: spore.spore_type,
+                x: spore.position.x,
+                y: spore.position.y,
+            })
+            .collect();
+        self.chunk.push(Frame {
+            tick: world.tick(),
+            spores,
+        });
+        if self.chunk.len() == FRAMES_PER_CHUNK {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> Result<(), TrajectoryError> {
+        let compressed =
+            snap::raw::Encoder::new().compress_vec(&bincode::serialize(&self.chunk)?)?;
+
+        self.index.push(ChunkEntry {
+            offset: self.offset,
+            first_frame: self.nr_frames,
+            first_tick: self.chunk[0].tick,
+            nr_frames: self.chunk.len(),
+        });
+        self.writer
+            .write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&compressed)?;
+
+        self.offset += 4 + compressed.len() as u64;
+        self.nr_frames += self.chunk.len();
+        self.chunk.clear();
+        Ok(())
+    }
+
+    /// Writes the last frames and the index.
+    pub fn finish(mut self) -> Result<(), TrajectoryError> {
+        if !self.chunk.is_empty() {
+            self.write_chunk()?;
+        }
+        bincode::serialize_into(&mut self.writer, &self.index)?;
+        self.writer.write_all(&self.offset.to_le_bytes())?;
+        self.writer.write_all(INDEX_MAGIC)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the frames of a trajectory file in any order, keeping the last decompressed chunk.
+pub struct TrajectoryReader {
+    reader: BufReader<File>,
+    header: TrajectoryHeader,
+    index: Vec<ChunkEntry>,
+    nr_frames: usize,
+    cached_chunk: Option<(usize, Vec<Frame>)>,
+}
+
+impl TrajectoryReader {
+    pub fn open(path: &Path) -> Result<TrajectoryReader, TrajectoryError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != TRAJECTORY_MAGIC {
+            return Err(TrajectoryError::NotATrajectory);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != TRAJECTORY_VERSION {
+            return Err(TrajectoryError::UnsupportedVersion(version));
+        }
+        let header_len = read_u64(&mut reader)?;
+        let mut header = vec![0; header_len as usize];
+        reader.read_exact(&mut header)?;
+        let header: TrajectoryHeader = bincode::deserialize(&header)?;
+        let chunks_start = reader.stream_position()?;
+
+        let index = match read_index(&mut reader)? {
+            Some(index) if is_contiguous(&index) => index,
+            _ => scan_chunks(&mut reader, chunks_start)?,
+        };
+        let nr_frames = index
+            .last()
+            .map(|entry| entry.first_frame + entry.nr_frames)
+            .unwrap_or(0);
+
+        Ok(TrajectoryReader {
+            reader,
+            header,
+            index,
+            nr_frames,
+            cached_chunk: None,
+        })
+    }
+
+    pub fn header(&self) -> &TrajectoryHeader {
+        &self.header
+    }
+
+    pub fn nr_frames(&self) -> usize {
+        self.nr_frames
+    }
+
+    /// The `frame_index`-th recorded frame.
+    pub fn frame(&mut self, frame_index: usize) -> Result<&Frame, TrajectoryError> {
+        if frame_index >= self.nr_frames {
+            return Err(TrajectoryError::NoSuchFrame(frame_index));
+        }
+        let chunk_index = self
+            .index
+            .partition_point(|entry| entry.first_frame + entry.nr_frames <= frame_index);
+        let first_frame = self.index[chunk_index].first_frame;
+        // a corrupt index can promise more frames than the chunk holds
+        self.load_chunk(chunk_index)?
+            .get(frame_index - first_frame)
+            .ok_or(TrajectoryError::NoSuchFrame(frame_index))
+    }
+
+    /// The index of the last frame recorded at or before `tick`, or the first frame.
+    pub fn frame_index_at_tick(&mut self, tick: u32) -> Result<usize, TrajectoryError> {
+        if self.index.is_empty() {
+            return Err(TrajectoryError::NoSuchFrame(0));
+        }
+        let chunk_index = self
+            .index
+            .partition_point(|entry| entry.first_tick <= tick)
+            .saturating_sub(1);
+        let first_frame = self.index[chunk_index].first_frame;
+        let frames_up_to_tick = self
+            .load_chunk(chunk_index)?
+            .partition_point(|frame| frame.tick <= tick);
+        Ok(first_frame + frames_up_to_tick.saturating_sub(1))
+    }
+
+    fn load_chunk(&mut self, chunk_index: usize) -> Result<&[Frame], TrajectoryError> {
+        let is_cached =
+            matches!(&self.cached_chunk, Some((cached_index, _)) if *cached_index == chunk_index);
+        if !is_cached {
+            let frames = read_chunk(&mut self.reader, self.index[chunk_index].offset)?;
+            self.cached_chunk = Some((chunk_index, frames));
+        }
+        Ok(&self.cached_chunk.as_ref().unwrap().1)
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_chunk(reader: &mut BufReader<File>, offset: u64) -> Result<Vec<Frame>, TrajectoryError> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let compressed_len = read_u32(reader)?;
+    let mut compressed = vec![0; compressed_len as usize];
+    reader.read_exact(&mut compressed)?;
+    let chunk = snap::raw::Decoder::new().decompress_vec(&compressed)?;
+    Ok(bincode::deserialize(&chunk)?)
+}
+
+fn read_index(reader: &mut BufReader<File>) -> Result<Option<Vec<ChunkEntry>>, TrajectoryError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    if file_len < 12 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::End(-12))?;
+    let index_offset = read_u64(reader)?;
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != INDEX_MAGIC || index_offset > file_len - 12 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(index_offset))?;
+    Ok(Some(bincode::deserialize_from(reader)?))
+}
+
+// every chunk starts at the frame after the previous one, so the frames can be looked up
+fn is_contiguous(index: &[ChunkEntry]) -> bool {
+    let mut nr_frames: usize = 0;
+    index.iter().all(|entry| {
+        let is_next = entry.first_frame == nr_frames && entry.nr_frames > 0;
+        match nr_frames.checked_add(entry.nr_frames) {
+            Some(total) if is_next => {
+                nr_frames = total;
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+// rebuilds the index of an interrupted recording, a truncated last chunk is ignored
+fn scan_chunks(
+    reader: &mut BufReader<File>,
+    chunks_start: u64,
+) -> Result<Vec<ChunkEntry>, TrajectoryError> {
+    let mut index = Vec::new();
+    let mut offset = chunks_start;
+    let mut nr_frames = 0;
+    while let Ok(frames) = read_chunk(reader, offset) {
+        let Some(first) = frames.first() else {
+            break;
+        };
+        index.push(ChunkEntry {
+            offset,
+            first_frame: nr_frames,
+            first_tick: first.tick,
+            nr_frames: frames.len(),
+        });
+        nr_frames += frames.len();
+        offset = reader.stream_position()?;
+    }
+    Ok(index)
+}
+
+#[derive(Debug)]
+pub enum TrajectoryError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    Compression(snap::Error),
+    NotATrajectory,
+    UnsupportedVersion(u32),
+    NoSuchFrame(usize),
+}
+
+impl From<io::Error> for TrajectoryError {
+    fn from(err: io::Error) -> Self {
+        TrajectoryError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for TrajectoryError {
+    fn from(err: bincode::Error) -> Self {
+        TrajectoryError::Encoding(err)
+    }
+}
+
+impl From<snap::Error> for TrajectoryError {
+    fn from(err: snap::Error) -> Self {
+        TrajectoryError::Compression(err)
+    }
+}
+
+impl fmt::Display for TrajectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrajectoryError::Io(err) => write!(f, "cannot access trajectory file: {}", err),
+            TrajectoryError::Encoding(err) => {
+                write!(f, "cannot encode or decode trajectory: {}", err)
+            }
+            TrajectoryError::Compression(err) => {
+                write!(f, "cannot (de)compress trajectory: {}", err)
+            }
+            TrajectoryError::NotATrajectory => write!(f, "not a pycniospores trajectory"),
+            TrajectoryError::UnsupportedVersion(version) => write!(
+                f,
+                "trajectory version {} is not supported, expected version {}",
+                version, TRAJECTORY_VERSION
+            ),
+            TrajectoryError::NoSuchFrame(frame_index) => {
+                write!(f, "the trajectory has no frame {}", frame_index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrajectoryError {}
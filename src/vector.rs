@@ -1,9 +1,10 @@
+use serde::{Deserialize, Serialize};
 use std::{
     iter::Sum,
-    ops::{Add, Div, Mul, Sub},
+    ops::{Add, AddAssign, Div, Mul, Sub, SubAssign},
 };
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Vector {
     pub x: f32,
     pub y: f32,
@@ -22,6 +23,12 @@ impl Add for Vector {
     }
 }
 
+impl AddAssign for Vector {
+    fn add_assign(&mut self, rhs: Vector) {
+        *self = *self + rhs;
+    }
+}
+
 impl Sub for Vector {
     type Output = Vector;
 
@@ -33,6 +40,12 @@ impl Sub for Vector {
     }
 }
 
+impl SubAssign for Vector {
+    fn sub_assign(&mut self, rhs: Vector) {
+        *self = *self - rhs;
+    }
+}
+
 impl Sub for &Vector {
     type Output = Vector;
 
@@ -0,0 +1,177 @@
+use rand::{rngs::StdRng, SeedableRng};
+use std::sync::Arc;
+
+use crate::{
+    boundary::apply_boundaries,
+    bucket::Grid,
+    configuration::SimulationConfig,
+    field::Fields,
+    generators::{generate_spore_configs, generate_spores},
+    obstacle::Obstacles,
+    spore::{Spore, SporeConfigs, SporesState},
+    spore_mover::move_spores,
+    thermostat::kinetic_temperature,
+    vector::Vector,
+};
+
+/// A running universe: the spores, their configurations and the number of ticks simulated so far.
+///
+/// A step is fully deterministic: worlds created with the same seed and number of spores
+/// stay bit-identical, tick after tick.
+pub struct World {
+    config: SimulationConfig,
+    spore_configs: SporeConfigs,
+    spores: SporesState,
+    tick: u32,
+}
+
+impl World {
+    /// Generates random spore configurations and places `nr_of_spores` spores randomly.
+    ///
+    /// Without a seed in the configuration a random one is picked and stored in the configuration,
+    /// so that every run can be reproduced.
+    ///
+    /// Panics if a mask obstacle or a gradient field can't be loaded, which `SimulationConfig::load`
+    /// checks.
+    pub fn new(mut config: SimulationConfig, nr_of_spores: u32) -> World {
+        let seed = *config.seed.get_or_insert_with(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let spore_configs = generate_spore_configs(&config, &mut rng);
+        let grid = Grid::new(&config, &spore_configs);
+        let obstacles = Obstacles::load(&config, &grid)
+            .unwrap_or_else(|err| panic!("cannot load the obstacles: {}", err));
+        let fields =
+            Fields::load(&config).unwrap_or_else(|err| panic!("cannot load the fields: {}", err));
+        let spores = generate_spores(
+            &config,
+            grid,
+            Arc::new(obstacles),
+            Arc::new(fields),
+            nr_of_spores,
+            &mut rng,
+        );
+        World::from_parts(config, spore_configs, spores, 0)
+    }
+
+    /// The spores must already be sorted into the buckets of `Grid::new(&config, &spore_configs)`.
+    pub fn from_parts(
+        config: SimulationConfig,
+        spore_configs: SporeConfigs,
+        spores: SporesState,
+        tick: u32,
+    ) -> World {
+        World {
+            config,
+            spore_configs,
+            spores,
+            tick,
+        }
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn spore_configs(&self) -> &SporeConfigs {
+        &self.spore_configs
+    }
+
+    pub fn state(&self) -> &SporesState {
+        &self.spores
+    }
+
+    pub fn obstacles(&self) -> &Obstacles {
+        &self.spores.obstacles
+    }
+
+    /// The buckets, sized from the longest force reach of the spore configurations.
+    pub fn grid(&self) -> &Grid {
+        &self.spores.grid
+    }
+
+    /// The seed of the run, if it was generated from one.
+    pub fn seed(&self) -> Option<u64> {
+        self.config.seed
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The temperature of the spores from their speeds and masses, see `kinetic_temperature`.
+    pub fn kinetic_temperature(&self) -> f32 {
+        kinetic_temperature(&self.spore_configs, &self.spores)
+    }
+
+    /// The simulated time: the number of ticks times `dt`.
+    pub fn time(&self) -> f64 {
+        self.spores.time
+    }
+
+    /// The number of reactions per reaction rule in the last tick.
+    pub fn reaction_counts(&self) -> Vec<u32> {
+        (0..self.config.reactions.len())
+            .map(|rule| {
+                self.spores
+                    .reaction_counts
+                    .get(rule)
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    pub fn nr_of_spores(&self) -> usize {
+        self.spores.len()
+    }
+
+    pub fn step(&mut self) {
+        move_spores(&self.config, &self.spore_configs, &mut self.spores);
+        self.tick += 1;
+    }
+
+    pub fn step_n(&mut self, nr_of_ticks: u32) {
+        for _ in 0..nr_of_ticks {
+            self.step();
+        }
+    }
+
+    /// Iterates over all spores, bucket by bucket (left to right, then top to bottom).
+    pub fn spores(&self) -> impl Iterator<Item = Spore> + '_ {
+        let state = &self.spores;
+        (0..state.len()).map(move |index| Spore {
+            id: state.ids[index],
+            position: state.positions[index],
+            speed: state.speeds[index],
+            spore_type: state.spore_types[index],
+        })
+    }
+
+    /// The positions of the two spores of every bond.
+    pub fn bonds(&self) -> impl Iterator<Item = (Vector, Vector)> + '_ {
+        let state = &self.spores;
+        let indexes = state.indexes_by_id();
+        state.bonds.iter().map(move |bond| {
+            let [a, b] = bond.ids.map(|id| state.positions[indexes[id as usize]]);
+            (a, b)
+        })
+    }
+
+    /// Adds a spore, positions outside of the universe are brought back in like a moving spore,
+    /// see `Boundary`. Returns the id of the new spore.
+    ///
+    /// Panics if `spore_type` is not one of the configured spore types.
+    pub fn insert_spore(&mut self, position: Vector, speed: Vector, spore_type: u8) -> u32 {
+        assert!(
+            (spore_type as usize) < self.config.number_of_configs,
+            "spore type {} is not configured",
+            spore_type
+        );
+        let (position, speed) = apply_boundaries(&self.config, position, speed);
+
+        let id = self.spores.push(position, speed, spore_type);
+        self.spores.sort_into_buckets();
+        id
+    }
+}
@@ -0,0 +1,207 @@
+use pycniospores::{
+    bond::BondRule,
+    snapshot::{load_snapshot, save_snapshot},
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+
+mod common;
+
+use common::{seeded, without_forces};
+
+fn rule(stiffness: f32, max_age: Option<f32>) -> BondRule {
+    BondRule {
+        types: [0, 0],
+        form_dist: 20.0,
+        form_probability: 1.0,
+        rest_length: 10.0,
+        stiffness,
+        break_stretch: 3.0,
+        max_age,
+    }
+}
+
+fn with_bond_rule(bond_rule: BondRule) -> SimulationConfig {
+    without_forces(SimulationConfig {
+        number_of_configs: 1,
+        bond_rules: vec![bond_rule],
+        ..seeded(7)
+    })
+}
+
+fn at(x: f32, y: f32) -> Vector {
+    Vector { x, y }
+}
+
+fn distance(world: &World) -> f32 {
+    let spores: Vec<Vector> = world.spores().map(|spore| spore.position).collect();
+    let (dx, dy) = (spores[1].x - spores[0].x, spores[1].y - spores[0].y);
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[test]
+fn bonded_spores_settle_at_the_rest_length() {
+    let mut world = World::new(with_bond_rule(rule(0.05, None)), 0);
+    world.insert_spore(at(1000.0, 1000.0), ZERO_VECTOR, 0);
+    world.insert_spore(at(1018.0, 1000.0), ZERO_VECTOR, 0);
+    world.step();
+    assert_eq!(world.state().bonds.len(), 1);
+    assert_eq!(world.state().bonds[0].ids, [0, 1]);
+    world.step_n(200);
+    assert!(
+        (distance(&world) - 10.0).abs() < 0.5,
+        "{}",
+        distance(&world)
+    );
+}
+
+#[test]
+fn a_spore_has_at_most_its_valence_in_bonds() {
+    let mut world = World::new(
+        SimulationConfig {
+            valences: vec![1],
+            ..with_bond_rule(rule(0.0, None))
+        },
+        0,
+    );
+    for x in [1000.0, 1005.0, 1010.0] {
+        world.insert_spore(at(x, 1000.0), ZERO_VECTOR, 0);
+    }
+    world.step_n(3);
+    assert_eq!(world.state().bonds.len(), 1);
+}
+
+#[test]
+fn bonds_break_when_stretched_or_old() {
+    // no friction and no spring: the spores drift apart at a constant speed
+    let mut world = World::new(
+        SimulationConfig {
+            friction: 1.0,
+            ..with_bond_rule(rule(0.0, None))
+        },
+        0,
+    );
+    world.insert_spore(at(1000.0, 1000.0), at(-1.0, 0.0), 0);
+    world.insert_spore(at(1015.0, 1000.0), at(1.0, 0.0), 0);
+    world.step();
+    assert_eq!(world.state().bonds.len(), 1);
+    // broken beyond 30.0, and too far apart to bond again
+    world.step_n(8);
+    assert!(distance(&world) > 30.0);
+    assert!(world.state().bonds.is_empty());
+
+    let mut world = World::new(with_bond_rule(rule(0.0, Some(5.0))), 0);
+    world.insert_spore(at(1000.0, 1000.0), ZERO_VECTOR, 0);
+    world.insert_spore(at(1010.0, 1000.0), ZERO_VECTOR, 0);
+    world.step();
+    let formed_at = world.state().bonds[0].formed_at;
+    world.step_n(5);
+    assert_eq!(world.state().bonds[0].formed_at, formed_at);
+    // broken when older than 5.0, and formed again in the next tick
+    world.step();
+    assert!(world.state().bonds.is_empty());
+    world.step();
+    assert_eq!(world.state().bonds[0].formed_at, world.time());
+}
+
+#[test]
+fn bonds_survive_the_move_to_other_buckets() {
+    let mut world = World::new(
+        SimulationConfig {
+            friction: 1.0,
+            ..with_bond_rule(rule(0.05, None))
+        },
+        0,
+    );
+    let speed = at(37.0, 23.0);
+    let ids = [
+        world.insert_spore(at(100.0, 100.0), speed, 0),
+        world.insert_spore(at(110.0, 100.0), speed, 0),
+    ];
+    world.step();
+    // across many buckets and around the universe
+    world.step_n(300);
+    assert_eq!(world.state().bonds.len(), 1);
+    assert_eq!(world.state().bonds[0].ids, ids);
+    assert!(
+        (distance(&world) - 10.0).abs() < 0.5,
+        "{}",
+        distance(&world)
+    );
+}
+
+#[test]
+fn bonds_are_reproducible_and_saved_in_snapshots() {
+    let bonding = SimulationConfig {
+        bond_rules: vec![BondRule {
+            form_probability: 0.1,
+            max_age: Some(40.0),
+            ..rule(0.02, None)
+        }],
+        valences: vec![2],
+        ..with_bond_rule(rule(0.0, None))
+    };
+    let run = |nr_of_ticks: u32| {
+        let mut world = World::new(bonding.clone(), 1000);
+        world.step_n(nr_of_ticks);
+        world
+    };
+    let world = run(30);
+    assert!(!world.state().bonds.is_empty());
+    assert_eq!(world.state().bonds, run(30).state().bonds);
+
+    let path = std::env::temp_dir().join(format!(
+        "pycniospores-bonds-{}.snapshot",
+        std::process::id()
+    ));
+    save_snapshot(&world, &path).unwrap();
+    let mut resumed = load_snapshot(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(resumed.state().bonds, world.state().bonds);
+    resumed.step_n(30);
+    assert_eq!(resumed.state().bonds, run(60).state().bonds);
+}
+
+#[test]
+fn bond_rules_are_validated() {
+    let invalid = |bond_rule: BondRule| with_bond_rule(bond_rule).validate().is_err();
+    assert!(with_bond_rule(rule(0.05, Some(10.0))).validate().is_ok());
+    assert!(invalid(BondRule {
+        types: [0, 1],
+        ..rule(0.05, None)
+    }));
+    assert!(invalid(BondRule {
+        form_probability: 1.5,
+        ..rule(0.05, None)
+    }));
+    assert!(invalid(BondRule {
+        break_stretch: 1.0,
+        ..rule(0.05, None)
+    }));
+    assert!(invalid(rule(0.05, Some(0.0))));
+    assert!(SimulationConfig {
+        valences: vec![1, 2],
+        ..with_bond_rule(rule(0.05, None))
+    }
+    .validate()
+    .is_err());
+
+    let with_rules = |bond_rules: Vec<BondRule>| SimulationConfig {
+        number_of_configs: 2,
+        bond_rules,
+        ..with_bond_rule(rule(0.05, None))
+    };
+    let between = |types: [u8; 2]| BondRule {
+        types,
+        ..rule(0.05, None)
+    };
+    assert!(with_rules(vec![between([0, 1]), between([1, 1])])
+        .validate()
+        .is_ok());
+    assert!(with_rules(vec![between([0, 1]), between([1, 0])])
+        .validate()
+        .is_err());
+    assert!(with_rules(vec![between([0, 0]), between([0, 0])])
+        .validate()
+        .is_err());
+}
@@ -0,0 +1,130 @@
+use pycniospores::{
+    boundary::{Boundaries, Boundary},
+    bucket::Grid,
+    movement_calculator::calc_forces,
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+
+mod common;
+
+use common::seeded;
+
+fn with_boundaries(x: Boundary, y: Boundary) -> SimulationConfig {
+    SimulationConfig {
+        boundaries: Boundaries { x, y },
+        ..seeded(17)
+    }
+}
+
+fn is_inside(world: &World, position: Vector) -> bool {
+    (0.0..=world.config().universe_width()).contains(&position.x)
+        && (0.0..=world.config().universe_height()).contains(&position.y)
+}
+
+#[test]
+fn walls_keep_the_spores_inside() {
+    for boundary in [Boundary::Reflecting, Boundary::SoftWall] {
+        let mut world = World::new(with_boundaries(boundary, boundary), 1000);
+        world.insert_spore(Vector { x: 1.0, y: 1.0 }, Vector { x: -30.0, y: -30.0 }, 0);
+        world.step_n(30);
+        assert_eq!(world.nr_of_spores(), 1001);
+        assert!(world
+            .spores()
+            .all(|spore| is_inside(&world, spore.position)));
+    }
+}
+
+#[test]
+fn absorbing_edges_remove_the_spores_leaving() {
+    let mut world = World::new(
+        with_boundaries(Boundary::Absorbing, Boundary::Periodic),
+        1000,
+    );
+    let leaving = world.insert_spore(Vector { x: 1.0, y: 1.0 }, Vector { x: -30.0, y: 0.0 }, 0);
+    world.step();
+    assert!(world.spores().all(|spore| spore.id != leaving));
+    assert!(world.nr_of_spores() < 1001);
+    assert!(world
+        .spores()
+        .all(|spore| is_inside(&world, spore.position)));
+}
+
+#[test]
+fn spores_only_feel_each_other_across_periodic_edges() {
+    let forces_across_the_edge = |x: Boundary| {
+        let mut world = World::new(with_boundaries(x, Boundary::Periodic), 0);
+        let width = world.config().universe_width();
+        world.insert_spore(Vector { x: 1.0, y: 100.0 }, ZERO_VECTOR, 0);
+        world.insert_spore(
+            Vector {
+                x: width - 1.0,
+                y: 100.0,
+            },
+            ZERO_VECTOR,
+            0,
+        );
+        let state = world.state();
+        calc_forces(world.config(), world.spore_configs(), state, 0..state.len())
+    };
+
+    assert!(forces_across_the_edge(Boundary::Periodic)
+        .iter()
+        .all(|force| force.x != 0.0));
+    assert!(forces_across_the_edge(Boundary::Reflecting)
+        .iter()
+        .all(|force| *force == ZERO_VECTOR));
+}
+
+#[test]
+fn soft_walls_push_the_spores_back() {
+    let mut world = World::new(with_boundaries(Boundary::SoftWall, Boundary::SoftWall), 0);
+    let (width, height) = (
+        world.config().universe_width(),
+        world.config().universe_height(),
+    );
+    world.insert_spore(Vector { x: 2.0, y: 400.0 }, ZERO_VECTOR, 0);
+    world.insert_spore(
+        Vector {
+            x: 400.0,
+            y: height - 2.0,
+        },
+        ZERO_VECTOR,
+        0,
+    );
+    world.insert_spore(
+        Vector {
+            x: width / 2.0,
+            y: height / 2.0,
+        },
+        ZERO_VECTOR,
+        0,
+    );
+    let state = world.state();
+    let forces = calc_forces(world.config(), world.spore_configs(), state, 0..state.len());
+
+    for (position, force) in state.positions.iter().zip(forces) {
+        if position.x < 10.0 {
+            assert!(force.x > 0.0 && force.y == 0.0);
+        } else if position.y > height - 10.0 {
+            assert!(force.y < 0.0 && force.x == 0.0);
+        } else {
+            assert_eq!(force, ZERO_VECTOR);
+        }
+    }
+}
+
+#[test]
+fn buckets_only_neighbor_across_periodic_edges() {
+    let world = World::new(with_boundaries(Boundary::Reflecting, Boundary::Periodic), 0);
+    let grid = Grid::new(world.config(), world.spore_configs());
+    let last_row = grid.nr_vert_buckets() - 1;
+
+    // the corner bucket: no neighbors on the left, but the bottom row is above the top row
+    let neighbors: Vec<_> = grid.get_neighbors((0, 0)).collect();
+    assert_eq!(neighbors.len(), 6);
+    assert!(neighbors.contains(&(1, last_row)));
+    assert!(neighbors
+        .iter()
+        .all(|(horz, _)| *horz != grid.nr_horz_buckets() - 1));
+}
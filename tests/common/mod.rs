@@ -0,0 +1,22 @@
+// the configurations the integration tests start from, not every test uses all of them
+#![allow(dead_code)]
+
+use pycniospores::SimulationConfig;
+
+/// The default configuration with a seed, so the spores of a test are the same every run.
+pub fn seeded(seed: u64) -> SimulationConfig {
+    SimulationConfig {
+        seed: Some(seed),
+        ..SimulationConfig::default()
+    }
+}
+
+/// The configuration without forces between the spores: they only move by their speed,
+/// or by what the test adds, like bonds, fields or walls.
+pub fn without_forces(config: SimulationConfig) -> SimulationConfig {
+    SimulationConfig {
+        max_force_amplitude: 0.0,
+        repulsion_amplitude: 0.0,
+        ..config
+    }
+}
@@ -0,0 +1,8 @@
+use pycniospores::SimulationConfig;
+use std::path::Path;
+
+#[test]
+fn sample_configuration_loads() {
+    let config = SimulationConfig::load(Path::new("pycniospores.toml")).unwrap();
+    assert_eq!(config.number_of_configs, 9);
+}
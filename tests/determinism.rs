@@ -0,0 +1,60 @@
+use pycniospores::{configuration::Interactions, spore::SporesState, SimulationConfig, World};
+
+const NR_OF_SPORES: u32 = 1500;
+const NR_OF_TICKS: u32 = 60;
+
+fn run(seed: u64) -> World {
+    run_with(seed, Interactions::Pairwise)
+}
+
+fn run_with(seed: u64, interactions: Interactions) -> World {
+    let config = SimulationConfig {
+        seed: Some(seed),
+        interactions,
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, NR_OF_SPORES);
+    world.step_n(NR_OF_TICKS);
+    world
+}
+
+// compares the bits, as 0.0 == -0.0 and NaN != NaN
+fn to_bits(state: &SporesState) -> Vec<(u32, u32, u32, u32, u8)> {
+    (0..state.len())
+        .map(|index| {
+            let position = state.positions[index];
+            let speed = state.speeds[index];
+            (
+                position.x.to_bits(),
+                position.y.to_bits(),
+                speed.x.to_bits(),
+                speed.y.to_bits(),
+                state.spore_types[index],
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_gives_bit_identical_state() {
+    let first = run(42);
+    let second = run(42);
+
+    assert_eq!(first.tick(), NR_OF_TICKS);
+    assert_eq!(first.nr_of_spores(), NR_OF_SPORES as usize);
+    assert_eq!(to_bits(first.state()), to_bits(second.state()));
+}
+
+#[test]
+fn symmetric_configs_give_bit_identical_state() {
+    let first = run_with(42, Interactions::Symmetric);
+    let second = run_with(42, Interactions::Symmetric);
+
+    assert!(first.spore_configs().is_symmetric());
+    assert_eq!(to_bits(first.state()), to_bits(second.state()));
+}
+
+#[test]
+fn different_seed_gives_different_state() {
+    assert_ne!(to_bits(run(42).state()), to_bits(run(43).state()));
+}
@@ -0,0 +1,183 @@
+use pycniospores::{
+    field::{FieldConfig, FieldShapeConfig, Modulation},
+    integrator::Integrator,
+    movement_calculator::calc_forces,
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+use std::{fs::File, io::BufWriter};
+
+mod common;
+
+use common::seeded;
+
+fn with_fields(fields: Vec<FieldConfig>) -> SimulationConfig {
+    SimulationConfig {
+        fields,
+        ..seeded(29)
+    }
+}
+
+fn constant(shape: FieldShapeConfig) -> FieldConfig {
+    FieldConfig {
+        shape,
+        modulation: Modulation::Constant,
+    }
+}
+
+// the force on a single spore at `position`, after `nr_of_ticks` ticks
+fn force_on_a_lone_spore(config: SimulationConfig, position: Vector, nr_of_ticks: u32) -> Vector {
+    let mut world = World::new(config, 0);
+    world.step_n(nr_of_ticks);
+    world.insert_spore(position, ZERO_VECTOR, 0);
+    let state = world.state();
+    calc_forces(world.config(), world.spore_configs(), state, 0..1)[0]
+}
+
+#[test]
+fn fields_push_a_lone_spore() {
+    let position = Vector {
+        x: 1000.0,
+        y: 1000.0,
+    };
+    let center = Vector {
+        x: 2000.0,
+        y: 1000.0,
+    };
+
+    let uniform = force_on_a_lone_spore(
+        with_fields(vec![constant(FieldShapeConfig::Uniform {
+            force: Vector { x: 0.0, y: 0.02 },
+        })]),
+        position,
+        0,
+    );
+    assert_eq!(uniform, Vector { x: 0.0, y: 0.02 });
+
+    let radial = force_on_a_lone_spore(
+        with_fields(vec![constant(FieldShapeConfig::Radial {
+            center,
+            strength: 0.1,
+        })]),
+        position,
+        0,
+    );
+    assert_eq!(radial, Vector { x: 0.1, y: 0.0 });
+
+    // clockwise on the screen: left of the center is up
+    let vortex = force_on_a_lone_spore(
+        with_fields(vec![constant(FieldShapeConfig::Vortex {
+            center,
+            strength: 0.1,
+        })]),
+        position,
+        0,
+    );
+    assert_eq!(vortex, Vector { x: 0.0, y: -0.1 });
+
+    // a quarter of the universe (3200 high) above its middle
+    let shear = force_on_a_lone_spore(
+        with_fields(vec![constant(FieldShapeConfig::Shear { strength: 0.1 })]),
+        Vector { x: 10.0, y: 800.0 },
+        0,
+    );
+    assert!(
+        (shear.x + 0.05).abs() < 1e-6 && shear.y == 0.0,
+        "{:?}",
+        shear
+    );
+}
+
+#[test]
+fn modulations_change_the_strength_over_time() {
+    let sine = Modulation::Sine {
+        period: 40.0,
+        phase: 0.0,
+    };
+    assert!((sine.factor(10.0) - 1.0).abs() < 1e-6);
+    assert!((sine.factor(30.0) + 1.0).abs() < 1e-6);
+
+    let pulse = Modulation::Pulse {
+        period: 10.0,
+        duty: 0.3,
+    };
+    assert_eq!(pulse.factor(12.0), 1.0);
+    assert_eq!(pulse.factor(15.0), 0.0);
+
+    let ramp = Modulation::Ramp { duration: 100.0 };
+    assert_eq!(ramp.factor(25.0), 0.25);
+    assert_eq!(ramp.factor(1000.0), 1.0);
+
+    // 10 ticks of dt = 0.5 is a quarter period of the sine
+    let sine_field = SimulationConfig {
+        dt: 0.5,
+        ..with_fields(vec![FieldConfig {
+            shape: FieldShapeConfig::Uniform {
+                force: Vector { x: 0.1, y: 0.0 },
+            },
+            modulation: Modulation::Sine {
+                period: 20.0,
+                phase: 0.0,
+            },
+        }])
+    };
+    let position = Vector { x: 500.0, y: 500.0 };
+    assert_eq!(
+        force_on_a_lone_spore(sine_field.clone(), position, 0).x,
+        0.0
+    );
+    let force = force_on_a_lone_spore(sine_field, position, 10);
+    assert!((force.x - 0.1).abs() < 1e-6, "{:?}", force);
+}
+
+#[test]
+fn the_time_advances_by_dt() {
+    for integrator in [Integrator::SemiImplicitEuler, Integrator::Rk4] {
+        let mut world = World::new(
+            SimulationConfig {
+                dt: 0.25,
+                integrator,
+                ..seeded(29)
+            },
+            100,
+        );
+        world.step_n(8);
+        assert_eq!(world.time(), 2.0);
+    }
+}
+
+#[test]
+fn spores_roll_down_a_gradient() {
+    // 32 x 4 pixels, from white on the left to black on the right
+    let path = std::env::temp_dir().join(format!("pycniospores-field-{}.png", std::process::id()));
+    let (width, height) = (32, 4);
+    let pixels: Vec<u8> = (0..width * height)
+        .map(|index| 255 - (index % width * 8) as u8)
+        .collect();
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(&path).unwrap()),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&pixels)
+        .unwrap();
+
+    let gradient = with_fields(vec![constant(FieldShapeConfig::Gradient {
+        path: path.to_str().unwrap().to_string(),
+        strength: 1.0,
+    })]);
+    let force = force_on_a_lone_spore(gradient, Vector { x: 2500.0, y: 10.0 }, 0);
+    std::fs::remove_file(&path).unwrap();
+
+    // 8 darker per pixel
+    assert!(
+        (force.x - 8.0 / 255.0).abs() < 1e-6 && force.y == 0.0,
+        "{:?}",
+        force
+    );
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "simd")]
+
+use pycniospores::{
+    boundary::{Boundaries, Boundary},
+    force_kernel::ForceKernel,
+    movement_calculator::calc_forces,
+    SimulationConfig, World,
+};
+
+#[test]
+fn simd_kernel_matches_the_scalar_one() {
+    assert_simd_matches_scalar(Boundaries::default());
+}
+
+#[test]
+fn simd_kernel_matches_the_scalar_one_without_wrap_around() {
+    assert_simd_matches_scalar(Boundaries {
+        x: Boundary::Reflecting,
+        y: Boundary::Absorbing,
+    });
+}
+
+fn assert_simd_matches_scalar(boundaries: Boundaries) {
+    let config = SimulationConfig {
+        seed: Some(11),
+        boundaries,
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, 2000);
+    // clumped spores, some of them closer than their repulsion distance
+    world.step_n(20);
+    let (scalar_config, spore_configs, state) =
+        (world.config(), world.spore_configs(), world.state());
+    let simd_config = SimulationConfig {
+        force_kernel: ForceKernel::Simd,
+        ..scalar_config.clone()
+    };
+    assert!(simd_config.force_kernel.uses_simd(&simd_config));
+
+    let all_spores = 0..state.len();
+    let scalar = calc_forces(scalar_config, spore_configs, state, all_spores.clone());
+    let simd = calc_forces(&simd_config, spore_configs, state, all_spores);
+    for (index, (scalar, simd)) in scalar.iter().zip(&simd).enumerate() {
+        let error = (scalar.x - simd.x).hypot(scalar.y - simd.y);
+        assert!(
+            error <= 1e-4 * (1.0 + scalar.x.hypot(scalar.y)),
+            "spore {}: {:?} scalar, {:?} simd",
+            index,
+            scalar,
+            simd
+        );
+    }
+}
+
+#[test]
+fn simd_kernel_falls_back_to_scalar() {
+    let config: SimulationConfig =
+        toml::from_str("force_kernel = \"simd\"\nforce_law = { kind = \"cosine_bump\" }").unwrap();
+    assert!(!config.force_kernel.uses_simd(&config));
+}
@@ -0,0 +1,84 @@
+use pycniospores::{
+    movement_calculator::{calc_force_from_bucket, calculate_forces_on_spore},
+    SimulationConfig, World,
+};
+
+fn world(universe_scale_factor: f32, max_force_reach: f32, nr_of_spores: u32) -> World {
+    let config = SimulationConfig {
+        seed: Some(5),
+        universe_scale_factor,
+        max_force_reach,
+        ..SimulationConfig::default()
+    };
+    assert!(config.validate().is_ok());
+    let mut world = World::new(config, nr_of_spores);
+    world.step_n(5);
+    world
+}
+
+// the forces found through the neighboring buckets must be the forces of all spores
+fn assert_grid_finds_all_forces(world: &World) {
+    let (config, spore_configs, state) = (world.config(), world.spore_configs(), world.state());
+    for index in 0..state.len() {
+        let (position, spore_type) = (state.positions[index], state.spore_types[index]);
+        let with_grid = calculate_forces_on_spore(
+            config,
+            spore_configs,
+            position,
+            spore_type,
+            state,
+            state.grid.get_bucket_from_pos(position),
+        );
+        // every spore as if they were all in a single bucket
+        let from_all = calc_force_from_bucket(
+            config,
+            spore_configs,
+            position,
+            spore_type,
+            &state.positions,
+            &state.spore_types,
+        );
+        let error = (with_grid.x - from_all.x).hypot(with_grid.y - from_all.y);
+        let size = from_all.x.hypot(from_all.y);
+        assert!(
+            error <= 1e-4 * (1.0 + size),
+            "spore {}: {:?} through the grid, {:?} from all spores",
+            index,
+            with_grid,
+            from_all
+        );
+    }
+}
+
+#[test]
+fn buckets_are_about_the_longest_force_reach() {
+    let world = world(2.0, 96.0, 1000);
+    let grid = world.grid();
+    let longest_reach = world
+        .spore_configs()
+        .force_reaches
+        .rows()
+        .flatten()
+        .fold(0.0, |max: f32, reach| max.max(*reach));
+
+    assert!(grid.bucket_width() >= longest_reach && grid.bucket_height() >= longest_reach);
+    assert!(grid.bucket_width() < 2.0 * longest_reach);
+    assert!(grid.bucket_height() < 2.0 * longest_reach);
+    assert_grid_finds_all_forces(&world);
+}
+
+#[test]
+fn two_buckets_on_an_axis() {
+    // 256 x 160, so 2 buckets high
+    let world = world(0.1, 64.0, 300);
+    assert_eq!(world.grid().nr_vert_buckets(), 2);
+    assert_grid_finds_all_forces(&world);
+}
+
+#[test]
+fn the_force_reach_of_half_the_universe() {
+    // 128 x 80 with the longest valid reach: a valid universe is never less than 2 buckets high
+    let world = world(0.05, 40.0, 200);
+    assert_eq!(world.grid().nr_vert_buckets(), 2);
+    assert_grid_finds_all_forces(&world);
+}
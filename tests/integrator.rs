@@ -0,0 +1,169 @@
+use pycniospores::{
+    bond::BondRule,
+    boundary::{Boundaries, Boundary},
+    field::{FieldConfig, FieldShapeConfig, Modulation},
+    integrator::Integrator,
+    movement_calculator::calc_forces,
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+use std::collections::HashMap;
+
+mod common;
+
+use common::{seeded, without_forces};
+
+#[test]
+fn semi_implicit_euler_at_dt_1_is_the_original_update() {
+    let config = SimulationConfig {
+        integrator: Integrator::SemiImplicitEuler,
+        dt: 1.0,
+        ..seeded(41)
+    };
+    let mut world = World::new(config, 2000);
+    world.step_n(10);
+
+    // speed * friction + force, then move with the new speed and wrap around, by id
+    let (config, state) = (world.config(), world.state());
+    let (width, height) = (config.universe_width(), config.universe_height());
+    let forces = calc_forces(config, world.spore_configs(), state, 0..state.len());
+    let expected: HashMap<u32, (Vector, Vector)> = (0..state.len())
+        .map(|index| {
+            let speed = state.speeds[index] * config.friction + forces[index];
+            let position = state.positions[index] + speed;
+            let position = Vector {
+                x: ((position.x % width) + width) % width,
+                y: ((position.y % height) + height) % height,
+            };
+            (state.ids[index], (position, speed))
+        })
+        .collect();
+
+    world.step();
+    assert_eq!(world.nr_of_spores(), expected.len());
+    for spore in world.spores() {
+        assert_eq!(
+            (spore.position, spore.speed),
+            expected[&spore.id],
+            "spore {}",
+            spore.id
+        );
+    }
+}
+
+// the kinetic energy of the spores and the potential energy of the spring between the first two
+fn energy(world: &World, stiffness: f32, rest_length: f32) -> f32 {
+    let spores: Vec<_> = world.spores().collect();
+    let kinetic: f32 = spores
+        .iter()
+        .map(|spore| (spore.speed.x.powi(2) + spore.speed.y.powi(2)) / 2.0)
+        .sum();
+    let length = (spores[1].position.x - spores[0].position.x)
+        .hypot(spores[1].position.y - spores[0].position.y);
+    kinetic + stiffness * (length - rest_length).powi(2) / 2.0
+}
+
+#[test]
+fn velocity_verlet_keeps_the_energy_of_a_spring() {
+    let (stiffness, rest_length) = (0.05, 10.0);
+    // without friction, only the bond pulls
+    let config = without_forces(SimulationConfig {
+        number_of_configs: 1,
+        bond_rules: vec![BondRule {
+            types: [0, 0],
+            form_dist: 20.0,
+            form_probability: 1.0,
+            rest_length,
+            stiffness,
+            break_stretch: 3.0,
+            max_age: None,
+        }],
+        integrator: Integrator::VelocityVerlet,
+        friction: 1.0,
+        dt: 0.25,
+        // a small universe, the spores are sorted into its buckets twice a tick
+        universe_scale_factor: 0.25,
+        ..seeded(43)
+    });
+    assert!(config.validate().is_ok());
+    let mut world = World::new(config, 0);
+    // stretched and spinning around each other
+    world.insert_spore(Vector { x: 300.0, y: 200.0 }, Vector { x: 0.0, y: 0.1 }, 0);
+    world.insert_spore(Vector { x: 318.0, y: 200.0 }, Vector { x: 0.0, y: -0.1 }, 0);
+    world.step();
+    assert_eq!(world.state().bonds.len(), 1);
+
+    let initial_energy = energy(&world, stiffness, rest_length);
+    // about 50 oscillations of the spring
+    for _ in 0..4000 {
+        world.step();
+        let energy = energy(&world, stiffness, rest_length);
+        assert!(
+            (energy - initial_energy).abs() < 0.01 * initial_energy,
+            "{} at tick {}, {} at the start",
+            energy,
+            world.tick(),
+            initial_energy
+        );
+    }
+    assert_eq!(world.state().bonds.len(), 1);
+}
+
+#[test]
+fn rk4_follows_a_lone_spore_in_a_uniform_field() {
+    let acceleration = Vector { x: 0.01, y: 0.02 };
+    let config = SimulationConfig {
+        fields: vec![FieldConfig {
+            shape: FieldShapeConfig::Uniform {
+                force: acceleration,
+            },
+            modulation: Modulation::Constant,
+        }],
+        // the wrap-around rounds the positions to the precision at the size of the universe
+        boundaries: Boundaries {
+            x: Boundary::Reflecting,
+            y: Boundary::Reflecting,
+        },
+        integrator: Integrator::Rk4,
+        friction: 0.9,
+        dt: 0.5,
+        ..seeded(47)
+    };
+    let mut world = World::new(config, 0);
+    let (position, speed) = (Vector { x: 100.0, y: 100.0 }, Vector { x: 1.0, y: -0.5 });
+    world.insert_spore(position, speed, 0);
+    world.step_n(100);
+    let spore = world.spores().next().unwrap();
+    assert_ne!(spore.speed, ZERO_VECTOR);
+
+    // dv/dt = acceleration - gamma * v: the speed relaxes to acceleration / gamma
+    let gamma = -(0.9_f64.ln());
+    let time = world.time();
+    let decay = (-gamma * time).exp();
+    let exact = |position: f32, speed: f32, acceleration: f32| {
+        let (speed, terminal_speed) = (speed as f64, acceleration as f64 / gamma);
+        (
+            position as f64
+                + terminal_speed * time
+                + (speed - terminal_speed) * (1.0 - decay) / gamma,
+            terminal_speed + (speed - terminal_speed) * decay,
+        )
+    };
+    for (actual, exact) in [
+        (
+            (spore.position.x, spore.speed.x),
+            exact(position.x, speed.x, acceleration.x),
+        ),
+        (
+            (spore.position.y, spore.speed.y),
+            exact(position.y, speed.y, acceleration.y),
+        ),
+    ] {
+        assert!(
+            (actual.0 as f64 - exact.0).abs() < 1e-3 && (actual.1 as f64 - exact.1).abs() < 1e-5,
+            "{:?} instead of {:?}",
+            actual,
+            exact
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use pycniospores::{
+    force_law::ForceLawKind,
+    generators::{previous_configs, PREVIOUS_NUMBER_OF_CONFIGS},
+    movement_calculator::calc_force_from_bucket,
+    vector::Vector,
+    SimulationConfig,
+};
+
+// the per-other-type configurations before the interaction matrix
+const REPULSION_DISTS: [f32; 9] = [9.82, 5.97, 9.16, 17.59, 17.57, 5.08, 8.20, 10.20, 12.20];
+const FORCE_FACTORS: [f32; 9] = [0.08, -0.04, 0.06, 0.07, 0.10, -0.08, 0.02, 0.04, -0.02];
+const FORCE_REACHES: [f32; 9] = [
+    27.41, 58.38, 38.75, 36.55, 52.39, 65.82, 30.05, 40.34, 45.98,
+];
+const REPULSION_AMPLITUDE: f32 = -5.0 * 0.15;
+
+const SPORE: Vector = Vector {
+    x: 1000.0,
+    y: 800.0,
+};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        force_law: ForceLawKind::Triangle,
+        ..SimulationConfig::default()
+    }
+}
+
+// the force of a spore of `other_type` at `dist` before the interaction matrix: only its type counted
+fn previous_force(other_type: usize, dist: Vector) -> Vector {
+    let scalar = (dist.x.powi(2) + dist.y.powi(2)).sqrt();
+    let repulsion_dist = REPULSION_DISTS[other_type];
+    if scalar < 0.000001 || scalar > FORCE_REACHES[other_type] {
+        Vector { x: 0.0, y: 0.0 }
+    } else if scalar < repulsion_dist {
+        dist * ((scalar - repulsion_dist).powi(2) * REPULSION_AMPLITUDE / repulsion_dist.powi(2))
+    } else {
+        let net_force_reach = FORCE_REACHES[other_type] - repulsion_dist;
+        let scale = (net_force_reach - (scalar - repulsion_dist)).abs() / net_force_reach / 2.0;
+        dist * (FORCE_FACTORS[other_type] * scale / scalar)
+    }
+}
+
+// the other spore at `dist`, or as close as the positions allow
+fn other_at(dist: Vector) -> Vector {
+    Vector {
+        x: SPORE.x + dist.x,
+        y: SPORE.y + dist.y,
+    }
+}
+
+fn force(spore_type: u8, other_type: u8, dist: Vector) -> Vector {
+    calc_force_from_bucket(
+        &config(),
+        &previous_configs(),
+        SPORE,
+        spore_type,
+        &[other_at(dist)],
+        &[other_type],
+    )
+}
+
+fn assert_close(actual: Vector, expected: Vector, what: &str) {
+    let error = (actual.x - expected.x).hypot(actual.y - expected.y);
+    assert!(
+        error <= 1e-9 + 1e-5 * expected.x.hypot(expected.y),
+        "{}: {:?} instead of {:?}",
+        what,
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn expanded_previous_configs_keep_the_previous_forces() {
+    assert_eq!(config().repulsion_amplitude, REPULSION_AMPLITUDE);
+    // repulsion, attraction and beyond the force reach, in several directions
+    let fractions_of_reach = [0.1, 0.3, 0.5, 0.75, 0.99, 1.2];
+    let directions = [(1.0, 0.0), (0.6, -0.8), (-0.28, 0.96)];
+    let pairs = [(0, 0), (0, 5), (3, 1), (4, 8), (5, 3), (7, 2), (8, 6)];
+    for &(spore_type, other_type) in &pairs {
+        for &fraction in &fractions_of_reach {
+            for &(x, y) in &directions {
+                let length = fraction * FORCE_REACHES[other_type];
+                let dist = other_at(Vector {
+                    x: x * length,
+                    y: y * length,
+                }) - SPORE;
+                assert_close(
+                    force(spore_type as u8, other_type as u8, dist),
+                    previous_force(other_type, dist),
+                    &format!("type {} from type {} at {:?}", spore_type, other_type, dist),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn the_force_only_depends_on_the_type_of_the_other_spore() {
+    let spore_configs = previous_configs();
+    assert_eq!(
+        spore_configs.number_of_configs(),
+        PREVIOUS_NUMBER_OF_CONFIGS
+    );
+
+    // halfway between the repulsion distance and the force reach of type 0: a quarter of its force factor
+    let halfway = Vector { x: 18.615, y: 0.0 };
+    // half the repulsion distance of type 5: a quarter of the repulsion amplitude per unit of distance
+    let repulsed = Vector { x: 0.0, y: 2.54 };
+    for spore_type in 0..PREVIOUS_NUMBER_OF_CONFIGS as u8 {
+        assert_close(
+            force(spore_type, 0, halfway),
+            Vector { x: 0.02, y: 0.0 },
+            &format!("type {} from type 0", spore_type),
+        );
+        assert_close(
+            force(spore_type, 5, repulsed),
+            Vector {
+                x: 0.0,
+                y: -0.47625,
+            },
+            &format!("type {} from type 5", spore_type),
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use pycniospores::{
+    field::{FieldConfig, FieldShapeConfig, Modulation},
+    generators::previous_configs,
+    thermostat::Thermostat,
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+
+mod common;
+
+use common::{seeded, without_forces};
+
+// of two spore types
+fn with_masses_and_radii(masses: Vec<f32>, radii: Vec<f32>) -> SimulationConfig {
+    SimulationConfig {
+        number_of_configs: 2,
+        masses,
+        radii,
+        ..seeded(37)
+    }
+}
+
+#[test]
+fn heavy_spores_accelerate_slower() {
+    let mut world = World::new(
+        SimulationConfig {
+            fields: vec![FieldConfig {
+                shape: FieldShapeConfig::Uniform {
+                    force: Vector { x: 0.4, y: 0.0 },
+                },
+                modulation: Modulation::Constant,
+            }],
+            ..with_masses_and_radii(vec![4.0, 1.0], Vec::new())
+        },
+        0,
+    );
+    let heavy = world.insert_spore(Vector { x: 100.0, y: 100.0 }, ZERO_VECTOR, 0);
+    let light = world.insert_spore(Vector { x: 100.0, y: 900.0 }, ZERO_VECTOR, 1);
+    world.step();
+    let speed_of = |id: u32| world.spores().find(|spore| spore.id == id).unwrap().speed;
+    assert_eq!(speed_of(heavy), Vector { x: 0.1, y: 0.0 });
+    assert_eq!(speed_of(light), Vector { x: 0.4, y: 0.0 });
+}
+
+#[test]
+fn spores_repel_each_other_until_they_touch() {
+    let world = World::new(with_masses_and_radii(Vec::new(), vec![9.0, 2.0]), 0);
+    let spore_configs = world.spore_configs();
+    assert!(spore_configs.repulsion_dists[(0, 0)] >= 18.0);
+    assert!(spore_configs.repulsion_dists[(0, 1)] >= 11.0);
+    assert!(spore_configs.repulsion_dists[(1, 1)] >= 4.0);
+    // printed to be pasted, with the masses and radii
+    assert!(spore_configs
+        .to_string()
+        .contains("masses = [1.00, 1.00]\nradii = [9.00, 2.00]"));
+
+    // without radii the spores are points, like before
+    let without = World::new(with_masses_and_radii(Vec::new(), Vec::new()), 0);
+    let mut with_zero_radii = with_masses_and_radii(Vec::new(), vec![0.0, 0.0]);
+    with_zero_radii.seed = without.seed();
+    assert_eq!(
+        World::new(with_zero_radii, 0).spore_configs(),
+        without.spore_configs()
+    );
+}
+
+#[test]
+fn masses_and_radii_are_validated() {
+    assert!(with_masses_and_radii(vec![1.0, 2.0, 3.0], Vec::new())
+        .validate()
+        .is_err());
+    assert!(with_masses_and_radii(vec![1.0, 0.0], Vec::new())
+        .validate()
+        .is_err());
+    assert!(with_masses_and_radii(Vec::new(), vec![1.0, -1.0])
+        .validate()
+        .is_err());
+    // the shortest random force reach is 0.2 * 96.0
+    assert!(with_masses_and_radii(Vec::new(), vec![9.0, 1.0])
+        .validate()
+        .is_ok());
+    assert!(with_masses_and_radii(Vec::new(), vec![10.0, 1.0])
+        .validate()
+        .is_err());
+
+    // a pasted table without masses and radii
+    let mut spore_configs = previous_configs();
+    spore_configs.masses.clear();
+    spore_configs.radii.clear();
+    let pasted = SimulationConfig {
+        seed: Some(1),
+        spore_configs: Some(spore_configs),
+        ..SimulationConfig::default()
+    };
+    assert!(pasted.validate().is_ok());
+    let world = World::new(pasted, 0);
+    assert_eq!(world.spore_configs().masses, vec![1.0; 9]);
+    assert_eq!(world.spore_configs().radii, vec![0.0; 9]);
+}
+
+#[test]
+fn heavy_and_light_spores_share_the_temperature() {
+    // free spores, only the thermostat changes their speeds
+    let mut world = World::new(
+        without_forces(SimulationConfig {
+            thermostat: Thermostat::Langevin {
+                temperature: 0.5,
+                damping: 0.2,
+                annealing: None,
+            },
+            ..with_masses_and_radii(vec![10.0, 1.0], Vec::new())
+        }),
+        2000,
+    );
+    world.step_n(100);
+    let temperature = world.kinetic_temperature();
+    assert!((temperature - 0.5).abs() < 0.05, "{}", temperature);
+
+    let mean_square_speed = |spore_type: u8| {
+        let speeds: Vec<f32> = world
+            .spores()
+            .filter(|spore| spore.spore_type == spore_type)
+            .map(|spore| spore.speed.x.powi(2) + spore.speed.y.powi(2))
+            .collect();
+        speeds.iter().sum::<f32>() / speeds.len() as f32
+    };
+    let ratio = mean_square_speed(1) / mean_square_speed(0);
+    assert!((7.0..13.0).contains(&ratio), "{}", ratio);
+}
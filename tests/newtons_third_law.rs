@@ -0,0 +1,145 @@
+use pycniospores::{
+    configuration::{Interactions, UpdateMode},
+    movement_calculator::{calc_force_from_bucket, calc_forces, calculate_forces_on_spore},
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+
+mod common;
+
+use common::seeded;
+
+fn symmetric_world(universe_scale_factor: f32, nr_of_spores: u32) -> World {
+    let config = SimulationConfig {
+        seed: Some(13),
+        interactions: Interactions::Symmetric,
+        universe_scale_factor,
+        max_force_reach: 64.0,
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, nr_of_spores);
+    world.step_n(20);
+    world
+}
+
+// every pair once gives the forces of every spore on every spore, and no net force
+fn assert_pairs_give_all_forces(world: &World) {
+    let (config, spore_configs, state) = (world.config(), world.spore_configs(), world.state());
+    assert!(spore_configs.is_symmetric());
+
+    let from_pairs = calc_forces(config, spore_configs, state, 0..state.len());
+    assert_eq!(from_pairs.len(), state.len());
+    let mut net_force = (0.0, 0.0);
+    for (index, from_pairs) in from_pairs.iter().enumerate() {
+        let position = state.positions[index];
+        let per_spore = calculate_forces_on_spore(
+            config,
+            spore_configs,
+            position,
+            state.spore_types[index],
+            state,
+            state.grid.get_bucket_from_pos(position),
+        );
+        let error = (from_pairs.x - per_spore.x).hypot(from_pairs.y - per_spore.y);
+        assert!(
+            error <= 1e-4 * (1.0 + per_spore.x.hypot(per_spore.y)),
+            "spore {}: {:?} from the pairs, {:?} per spore",
+            index,
+            from_pairs,
+            per_spore
+        );
+        net_force = (net_force.0 + from_pairs.x, net_force.1 + from_pairs.y);
+    }
+    assert!(net_force.0.hypot(net_force.1) < 1e-3, "{:?}", net_force);
+}
+
+#[test]
+fn symmetric_forces_are_calculated_per_pair() {
+    assert_pairs_give_all_forces(&symmetric_world(2.0, 2000));
+}
+
+#[test]
+fn pairs_with_two_buckets_on_an_axis() {
+    let world = symmetric_world(0.1, 300);
+    assert_eq!(world.grid().nr_vert_buckets(), 2);
+    assert_pairs_give_all_forces(&world);
+}
+
+#[test]
+fn random_pairwise_configs_are_not_symmetric() {
+    let config = SimulationConfig {
+        seed: Some(13),
+        ..SimulationConfig::default()
+    };
+    assert!(!World::new(config, 10).spore_configs().is_symmetric());
+}
+
+// two resting spores of the same type on either side of the border between the first two buckets,
+// half their force reach apart
+fn mirrored_spores(update_mode: UpdateMode) -> (World, [Vector; 2]) {
+    let config = SimulationConfig {
+        number_of_configs: 1,
+        update_mode,
+        ..seeded(13)
+    };
+    let mut world = World::new(config, 0);
+    let (border, y) = (
+        world.grid().bucket_width(),
+        world.grid().bucket_height() / 2.0,
+    );
+    let half_dist = world.spore_configs().force_reaches[(0, 0)] / 4.0;
+    let positions = [
+        Vector {
+            x: border - half_dist,
+            y,
+        },
+        Vector {
+            x: border + half_dist,
+            y,
+        },
+    ];
+    for position in positions {
+        world.insert_spore(position, ZERO_VECTOR, 0);
+    }
+    // as stored, after the wrap-around
+    let positions: Vec<Vector> = world.spores().map(|spore| spore.position).collect();
+    assert_eq!(world.grid().get_bucket_from_pos(positions[0]), (0, 0));
+    assert_eq!(world.grid().get_bucket_from_pos(positions[1]), (1, 0));
+    (world, [positions[0], positions[1]])
+}
+
+fn speeds(world: &World) -> Vec<Vector> {
+    world.spores().map(|spore| spore.speed).collect()
+}
+
+#[test]
+fn mirrored_spores_get_opposite_speeds() {
+    let (mut world, _) = mirrored_spores(UpdateMode::Synchronous);
+    world.step();
+    let speeds = speeds(&world);
+    assert_ne!(speeds[0], ZERO_VECTOR);
+    assert_eq!(speeds[0], speeds[1] * -1.0);
+}
+
+#[test]
+fn sequential_buckets_feel_the_moved_spores() {
+    let (mut world, [first, second]) = mirrored_spores(UpdateMode::Sequential);
+    let (config, spore_configs) = (world.config().clone(), world.spore_configs().clone());
+    let force_on = |position: Vector, other: Vector| {
+        calc_force_from_bucket(&config, &spore_configs, position, 0, &[other], &[0])
+    };
+    // the first bucket moves first, the second one feels the first spore where it moved to
+    let first_speed = force_on(first, second);
+    let (width, height) = (config.universe_width(), config.universe_height());
+    let moved = first + first_speed;
+    let moved = Vector {
+        x: ((moved.x % width) + width) % width,
+        y: ((moved.y % height) + height) % height,
+    };
+    let second_speed = force_on(second, moved);
+
+    world.step();
+    let speeds = speeds(&world);
+    assert_eq!(speeds, vec![first_speed, second_speed]);
+    assert_ne!(speeds[0], speeds[1] * -1.0);
+}
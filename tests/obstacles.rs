@@ -0,0 +1,177 @@
+use pycniospores::{
+    configuration::ConfigError,
+    obstacle::ObstacleConfig,
+    snapshot::{load_snapshot, save_snapshot},
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+use std::{fs::File, io::BufWriter, path::Path};
+
+mod common;
+
+use common::seeded;
+
+fn with_obstacles(obstacles: Vec<ObstacleConfig>) -> SimulationConfig {
+    SimulationConfig {
+        obstacles,
+        ..seeded(23)
+    }
+}
+
+fn assert_outside_of_the_obstacles(world: &World) {
+    let (grid, obstacles) = (world.grid(), world.obstacles());
+    for spore in world.spores() {
+        assert!(
+            !obstacles.contains(grid, spore.position),
+            "spore {} inside an obstacle at {:?}",
+            spore.id,
+            spore.position
+        );
+    }
+}
+
+#[test]
+fn spores_stay_outside_of_the_obstacles() {
+    let world_with = |obstacles| {
+        let mut world = World::new(with_obstacles(obstacles), 2000);
+        assert_outside_of_the_obstacles(&world);
+        world.step_n(40);
+        assert_outside_of_the_obstacles(&world);
+    };
+
+    world_with(vec![ObstacleConfig::Circle {
+        center: Vector {
+            x: 2560.0,
+            y: 1600.0,
+        },
+        radius: 400.0,
+    }]);
+    world_with(vec![ObstacleConfig::Rectangle {
+        min: Vector {
+            x: 1000.0,
+            y: 800.0,
+        },
+        max: Vector {
+            x: 3000.0,
+            y: 1200.0,
+        },
+    }]);
+    world_with(vec![ObstacleConfig::Segment {
+        from: Vector { x: 500.0, y: 500.0 },
+        to: Vector {
+            x: 4500.0,
+            y: 2500.0,
+        },
+        thickness: 60.0,
+    }]);
+}
+
+#[test]
+fn fast_spores_do_not_jump_over_thin_walls() {
+    let mut world = World::new(
+        with_obstacles(vec![ObstacleConfig::Segment {
+            from: Vector { x: 1000.0, y: 0.0 },
+            to: Vector {
+                x: 1000.0,
+                y: 3200.0,
+            },
+            thickness: 2.0,
+        }]),
+        0,
+    );
+    let id = world.insert_spore(Vector { x: 990.0, y: 500.0 }, Vector { x: 50.0, y: 0.0 }, 0);
+    world.step_n(5);
+    let spore = world.spores().find(|spore| spore.id == id).unwrap();
+    assert!(spore.position.x < 1000.0, "{:?}", spore.position);
+    assert!(spore.speed.x < 0.0);
+}
+
+#[test]
+fn dark_pixels_of_a_mask_are_solid() {
+    // 16 x 10 pixels, the left half black
+    let path = std::env::temp_dir().join(format!("pycniospores-{}.png", std::process::id()));
+    let (width, height) = (16, 10);
+    let pixels: Vec<u8> = (0..width * height)
+        .map(|index| if index % width < width / 2 { 0 } else { 255 })
+        .collect();
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path).unwrap()), 16, 10);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&pixels)
+        .unwrap();
+
+    let mut world = World::new(
+        with_obstacles(vec![ObstacleConfig::Mask {
+            path: path.to_str().unwrap().to_string(),
+        }]),
+        1000,
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    let middle = world.config().universe_width() / 2.0;
+    world.step_n(20);
+    assert!(world.spores().all(|spore| spore.position.x >= middle));
+    assert_outside_of_the_obstacles(&world);
+}
+
+#[test]
+fn a_missing_mask_is_a_configuration_error() {
+    let path = std::env::temp_dir().join(format!("pycniospores-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        "[[obstacles]]\nmask = { path = \"/nonexistent/mask.png\" }\n",
+    )
+    .unwrap();
+    let result = SimulationConfig::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(ConfigError::Invalid(_))));
+}
+
+#[test]
+fn snapshots_keep_the_obstacles() {
+    let mut world = World::new(
+        with_obstacles(vec![
+            ObstacleConfig::Circle {
+                center: Vector { x: 800.0, y: 800.0 },
+                radius: 100.0,
+            },
+            ObstacleConfig::Segment {
+                from: Vector { x: 100.0, y: 100.0 },
+                to: Vector { x: 100.0, y: 900.0 },
+                thickness: 10.0,
+            },
+        ]),
+        500,
+    );
+    world.step_n(3);
+    let path = std::env::temp_dir().join(format!(
+        "pycniospores-obstacles-{}.snapshot",
+        std::process::id()
+    ));
+    save_snapshot(&world, &path).unwrap();
+    let loaded = load_snapshot(Path::new(&path)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.obstacles(), world.obstacles());
+    assert!(loaded
+        .obstacles()
+        .contains(loaded.grid(), Vector { x: 800.0, y: 800.0 }));
+    assert!(!loaded.obstacles().contains(loaded.grid(), ZERO_VECTOR));
+}
+
+#[test]
+fn spores_without_a_free_place_are_left_out() {
+    let universe = SimulationConfig::default();
+    let all_of_the_universe = ObstacleConfig::Rectangle {
+        min: Vector { x: -1.0, y: -1.0 },
+        max: Vector {
+            x: universe.universe_width() + 1.0,
+            y: universe.universe_height() + 1.0,
+        },
+    };
+    let world = World::new(with_obstacles(vec![all_of_the_universe]), 20);
+    assert_eq!(world.nr_of_spores(), 0);
+}
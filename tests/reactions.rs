@@ -0,0 +1,137 @@
+use pycniospores::{
+    reaction::ReactionRule,
+    snapshot::{load_snapshot, save_snapshot},
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+
+mod common;
+
+use common::{seeded, without_forces};
+
+fn rule(reactants: [u8; 2], products: Vec<u8>, contact_time: f32) -> ReactionRule {
+    ReactionRule {
+        reactants,
+        products,
+        dist: 12.0,
+        contact_time,
+        probability: 1.0,
+    }
+}
+
+fn with_reactions(reactions: Vec<ReactionRule>) -> SimulationConfig {
+    without_forces(SimulationConfig {
+        number_of_configs: 3,
+        reactions,
+        ..seeded(11)
+    })
+}
+
+fn world_with(config: SimulationConfig, spore_types: &[u8]) -> World {
+    let mut world = World::new(config, 0);
+    for (index, spore_type) in spore_types.iter().enumerate() {
+        let position = Vector {
+            x: 1000.0 + 5.0 * index as f32,
+            y: 1000.0,
+        };
+        world.insert_spore(position, ZERO_VECTOR, *spore_type);
+    }
+    world
+}
+
+fn spore_types(world: &World) -> Vec<u8> {
+    let mut spores: Vec<(u32, u8)> = world
+        .spores()
+        .map(|spore| (spore.id, spore.spore_type))
+        .collect();
+    spores.sort_unstable();
+    spores
+        .into_iter()
+        .map(|(_, spore_type)| spore_type)
+        .collect()
+}
+
+#[test]
+fn a_catalyst_turns_a_spore_after_the_contact_time() {
+    // B is in front of A, the reactants can be in any order
+    let mut world = world_with(with_reactions(vec![rule([0, 1], vec![2, 1], 3.0)]), &[1, 0]);
+    world.step_n(3);
+    assert_eq!(spore_types(&world), [1, 0]);
+    assert_eq!(world.reaction_counts(), [0]);
+    world.step();
+    assert_eq!(spore_types(&world), [1, 2]);
+    assert_eq!(world.reaction_counts(), [1]);
+    world.step();
+    assert_eq!(world.reaction_counts(), [0]);
+}
+
+#[test]
+fn a_reaction_can_use_up_a_spore() {
+    let mut world = world_with(with_reactions(vec![rule([0, 0], vec![1], 0.0)]), &[0, 0]);
+    world.step();
+    assert_eq!(spore_types(&world), [1]);
+    assert_eq!(world.nr_of_spores(), 1);
+    assert!(world.state().is_consistent());
+}
+
+#[test]
+fn a_spore_reacts_at_most_once_per_tick() {
+    let mut world = world_with(with_reactions(vec![rule([0, 0], vec![1], 0.0)]), &[0, 0, 0]);
+    world.step();
+    assert_eq!(world.reaction_counts(), [1]);
+    assert_eq!(world.nr_of_spores(), 2);
+    world.step_n(3);
+    assert_eq!(world.nr_of_spores(), 2);
+}
+
+#[test]
+fn reactions_are_reproducible_and_saved_in_snapshots() {
+    let reacting = with_reactions(vec![
+        ReactionRule {
+            probability: 0.2,
+            ..rule([0, 1], vec![2, 1], 5.0)
+        },
+        ReactionRule {
+            dist: 24.0,
+            probability: 0.05,
+            ..rule([2, 2], vec![0], 2.0)
+        },
+    ]);
+    let run = |nr_of_ticks: u32| {
+        let mut world = World::new(reacting.clone(), 2000);
+        world.step_n(nr_of_ticks);
+        world
+    };
+    let world = run(20);
+    assert!(!world.state().contacts.is_empty());
+    assert_eq!(spore_types(&world), spore_types(&run(20)));
+
+    let path = std::env::temp_dir().join(format!(
+        "pycniospores-reactions-{}.snapshot",
+        std::process::id()
+    ));
+    save_snapshot(&world, &path).unwrap();
+    let mut resumed = load_snapshot(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    resumed.step_n(20);
+    let original = run(40);
+    assert!(original.nr_of_spores() < 2000);
+    assert_eq!(spore_types(&resumed), spore_types(&original));
+    assert_eq!(resumed.reaction_counts(), original.reaction_counts());
+}
+
+#[test]
+fn reactions_are_validated() {
+    let invalid = |reaction: ReactionRule| with_reactions(vec![reaction]).validate().is_err();
+    assert!(with_reactions(vec![rule([0, 1], vec![2, 1], 3.0)])
+        .validate()
+        .is_ok());
+    assert!(invalid(rule([0, 3], vec![2, 1], 0.0)));
+    assert!(invalid(rule([0, 1], vec![], 0.0)));
+    assert!(invalid(rule([0, 1], vec![2, 1, 0], 0.0)));
+    assert!(invalid(rule([0, 1], vec![2, 1], -1.0)));
+    assert!(invalid(ReactionRule {
+        probability: 1.5,
+        ..rule([0, 1], vec![2, 1], 0.0)
+    }));
+}
@@ -0,0 +1,109 @@
+use pycniospores::{
+    force_law::ForceLawKind,
+    snapshot::{load_snapshot, save_snapshot, SnapshotError},
+    spore::{SporeConfigs, SporesState},
+    SimulationConfig, World,
+};
+use std::path::PathBuf;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "pycniospores-{}-{}.snapshot",
+        name,
+        std::process::id()
+    ))
+}
+
+// saves and loads the world, then steps both for 10 ticks
+fn assert_resumes_like_the_original(mut world: World, name: &str) {
+    let path = snapshot_path(name);
+    save_snapshot(&world, &path).unwrap();
+    let mut resumed = load_snapshot(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    world.step_n(10);
+    resumed.step_n(10);
+    assert_eq!(resumed.tick(), world.tick());
+    assert_eq!(resumed.config().force_law, world.config().force_law);
+    assert!(resumed.spores().eq(world.spores()));
+}
+
+#[test]
+fn resumed_snapshot_continues_like_the_original() {
+    let mut world = World::new(toml::from_str("seed = 3").unwrap(), 500);
+    world.step_n(10);
+    assert_resumes_like_the_original(world, "resumed");
+}
+
+#[test]
+fn a_world_without_a_seed_can_be_resumed() {
+    let mut world = World::new(toml::from_str("seed = 8").unwrap(), 300);
+    world.step_n(5);
+    let config = SimulationConfig {
+        seed: None,
+        ..world.config().clone()
+    };
+    let world = World::from_parts(
+        config,
+        world.spore_configs().clone(),
+        world.state().clone(),
+        world.tick(),
+    );
+    assert_resumes_like_the_original(world, "seedless");
+}
+
+#[test]
+fn resumed_snapshot_keeps_its_force_law() {
+    let config: SimulationConfig = toml::from_str(
+        "seed = 3\nforce_law = { kind = \"morse\", width = 0.2 }\nnumber_of_spores = 500",
+    )
+    .unwrap();
+    assert_eq!(config.force_law, ForceLawKind::Morse { width: 0.2 });
+
+    let mut world = World::new(config, 500);
+    world.step_n(10);
+    assert_resumes_like_the_original(world, "morse");
+}
+
+#[test]
+fn force_law_parameters_must_match_the_kind() {
+    assert!(toml::from_str::<SimulationConfig>("force_law = { kind = \"morse\" }").is_err());
+    assert!(
+        toml::from_str::<SimulationConfig>("force_law = { kind = \"triangle\", width = 0.2 }")
+            .is_err()
+    );
+}
+
+// what a snapshot file holds after its magic bytes and version
+type SnapshotParts = (SimulationConfig, SporeConfigs, SporesState, u32);
+
+#[test]
+fn corrupt_snapshots_are_invalid() {
+    let world = World::new(toml::from_str("seed = 4").unwrap(), 200);
+    let path = snapshot_path("corrupt");
+    save_snapshot(&world, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let (header, body) = bytes.split_at(8);
+    let parts: SnapshotParts = bincode::deserialize(body).unwrap();
+
+    let corruptions: [fn(&mut SnapshotParts); 4] = [
+        |(_, _, spores, _)| spores.spore_types[0] = 200,
+        |(_, spore_configs, _, _)| {
+            spore_configs.masses.pop();
+        },
+        |(_, spore_configs, _, _)| spore_configs.force_reaches[(0, 1)] = f32::NAN,
+        |(_, spore_configs, _, _)| spore_configs.masses[2] = 0.0,
+    ];
+    for corrupt in corruptions {
+        let mut parts = parts.clone();
+        corrupt(&mut parts);
+        let mut corrupted = header.to_vec();
+        corrupted.extend(bincode::serialize(&parts).unwrap());
+        std::fs::write(&path, corrupted).unwrap();
+        assert!(matches!(
+            load_snapshot(&path),
+            Err(SnapshotError::Invalid(_))
+        ));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
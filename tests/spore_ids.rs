@@ -0,0 +1,30 @@
+use pycniospores::{
+    vector::{Vector, ZERO_VECTOR},
+    SimulationConfig, World,
+};
+use std::collections::HashMap;
+
+fn types_by_id(world: &World) -> HashMap<u32, u8> {
+    world
+        .spores()
+        .map(|spore| (spore.id, spore.spore_type))
+        .collect()
+}
+
+#[test]
+fn spores_keep_their_id_while_moving_between_buckets() {
+    let config = SimulationConfig {
+        seed: Some(7),
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, 1000);
+    let before = types_by_id(&world);
+    assert_eq!(before.len(), 1000);
+
+    world.step_n(30);
+    assert_eq!(types_by_id(&world), before);
+
+    let id = world.insert_spore(Vector { x: 10.0, y: 10.0 }, ZERO_VECTOR, 0);
+    assert!(!before.contains_key(&id));
+    assert_eq!(types_by_id(&world).len(), 1001);
+}
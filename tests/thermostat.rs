@@ -0,0 +1,105 @@
+use pycniospores::{
+    integrator::Integrator,
+    thermostat::{thermal_noise, Annealing, Thermostat},
+    SimulationConfig, World,
+};
+use std::collections::HashSet;
+
+mod common;
+
+use common::{seeded, without_forces};
+
+// free spores, only the thermostat changes their speeds
+fn langevin(temperature: f32, annealing: Option<Annealing>) -> SimulationConfig {
+    without_forces(SimulationConfig {
+        thermostat: Thermostat::Langevin {
+            temperature,
+            damping: 0.2,
+            annealing,
+        },
+        ..seeded(31)
+    })
+}
+
+#[test]
+fn free_spores_reach_the_temperature() {
+    for integrator in [
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ] {
+        let mut world = World::new(
+            SimulationConfig {
+                integrator,
+                ..langevin(0.5, None)
+            },
+            2000,
+        );
+        assert_eq!(world.kinetic_temperature(), 0.0);
+        world.step_n(100);
+        let temperature = world.kinetic_temperature();
+        assert!(
+            (temperature - 0.5).abs() < 0.05,
+            "{:?}: {}",
+            integrator,
+            temperature
+        );
+    }
+}
+
+#[test]
+fn the_noise_is_reproducible_from_the_seed() {
+    let run = |seed: u64| {
+        let mut world = World::new(
+            SimulationConfig {
+                seed: Some(seed),
+                max_force_amplitude: 0.15,
+                repulsion_amplitude: -0.75,
+                ..langevin(0.1, None)
+            },
+            1000,
+        );
+        world.step_n(20);
+        world.spores().map(|spore| spore.speed).collect::<Vec<_>>()
+    };
+    assert_eq!(run(5), run(5));
+    assert_ne!(run(5), run(6));
+}
+
+#[test]
+fn different_seeds_dont_share_noise() {
+    let noises = |seed: u64| {
+        let config = SimulationConfig {
+            seed: Some(seed),
+            ..langevin(0.1, None)
+        };
+        (0..1000)
+            .map(|id| {
+                let noise = thermal_noise(&config, id, 3.0, 1.0);
+                (noise.x.to_bits(), noise.y.to_bits())
+            })
+            .collect::<HashSet<_>>()
+    };
+    let (a, b) = (noises(5), noises(6));
+    assert_eq!(a.len(), 1000);
+    assert!(a.is_disjoint(&b));
+}
+
+#[test]
+fn annealing_cools_the_spores_down() {
+    let annealing = Annealing {
+        final_temperature: 0.0,
+        duration: 100.0,
+    };
+    let thermostat = langevin(1.0, Some(annealing)).thermostat;
+    assert_eq!(thermostat.temperature(0.0), 1.0);
+    assert_eq!(thermostat.temperature(25.0), 0.75);
+    assert_eq!(thermostat.temperature(500.0), 0.0);
+
+    let mut world = World::new(langevin(1.0, Some(annealing)), 2000);
+    world.step_n(50);
+    let hot = world.kinetic_temperature();
+    world.step_n(100);
+    let cold = world.kinetic_temperature();
+    assert!(hot > 0.3 && cold < 0.01, "{} then {}", hot, cold);
+}
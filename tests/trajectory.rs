@@ -0,0 +1,233 @@
+use pycniospores::{
+    trajectory::{TrajectoryReader, TrajectoryRecorder},
+    SimulationConfig, World,
+};
+use std::{
+    convert::TryInto,
+    path::{Path, PathBuf},
+};
+
+const RECORD_INTERVAL: u32 = 3;
+// more than one chunk of 32 frames
+const NR_OF_TICKS: u32 = 120;
+
+fn trajectory_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "pycniospores-{}-{}.trajectory",
+        name,
+        std::process::id()
+    ))
+}
+
+// (tick, id, type, x, y) of every recorded spore, per frame
+type Frames = Vec<Vec<(u32, u32, u8, f32, f32)>>;
+
+fn spores_of(world: &World) -> Vec<(u32, u32, u8, f32, f32)> {
+    world
+        .spores()
+        .map(|spore| {
+            (
+                world.tick(),
+                spore.id,
+                spore.spore_type,
+                spore.position.x,
+                spore.position.y,
+            )
+        })
+        .collect()
+}
+
+/// Records a seeded world from `first_tick` on, returns the world and the frames that should be recorded.
+fn record(path: &Path, first_tick: u32) -> (World, Frames) {
+    let config = SimulationConfig {
+        seed: Some(19),
+        ..SimulationConfig::default()
+    };
+    let mut world = World::new(config, 300);
+    world.step_n(first_tick);
+
+    let mut recorder = TrajectoryRecorder::create(path, &world, RECORD_INTERVAL).unwrap();
+    let mut frames = vec![spores_of(&world)];
+    for _ in 0..NR_OF_TICKS {
+        world.step();
+        recorder.record(&world).unwrap();
+        if world.tick().is_multiple_of(RECORD_INTERVAL) {
+            frames.push(spores_of(&world));
+        }
+    }
+    recorder.finish().unwrap();
+    (world, frames)
+}
+
+fn read_frame(reader: &mut TrajectoryReader, frame_index: usize) -> Vec<(u32, u32, u8, f32, f32)> {
+    let frame = reader.frame(frame_index).unwrap();
+    frame
+        .spores
+        .iter()
+        .map(|spore| (frame.tick, spore.id, spore.spore_type, spore.x, spore.y))
+        .collect()
+}
+
+// where the chunks start: after the magic bytes, the version, the header length and the header
+fn chunks_start(bytes: &[u8]) -> usize {
+    16 + u64::from_le_bytes(bytes[8..16].try_into().unwrap()) as usize
+}
+
+// where the index starts, as written in the footer
+fn index_offset(bytes: &[u8]) -> usize {
+    let footer = bytes.len() - 12;
+    u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap()) as usize
+}
+
+#[test]
+fn every_recorded_frame_is_read_back() {
+    let path = trajectory_path("frames");
+    let (_, frames) = record(&path, 0);
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(reader.nr_frames(), frames.len());
+    for (frame_index, frame) in frames.iter().enumerate() {
+        assert_eq!(&read_frame(&mut reader, frame_index), frame);
+    }
+    assert!(reader.frame(frames.len()).is_err());
+}
+
+#[test]
+fn the_header_describes_the_recorded_world() {
+    let path = trajectory_path("header");
+    let (world, _) = record(&path, 0);
+    let reader = TrajectoryReader::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let header = reader.header();
+    assert_eq!(&header.spore_configs, world.spore_configs());
+    assert_eq!(header.universe_width, world.config().universe_width());
+    assert_eq!(header.universe_height, world.config().universe_height());
+    assert_eq!(header.seed, world.seed());
+    assert_eq!(header.record_interval, RECORD_INTERVAL);
+}
+
+#[test]
+fn an_interrupted_recording_is_scanned() {
+    let path = trajectory_path("interrupted");
+    let (_, frames) = record(&path, 0);
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..index_offset(&bytes)]).unwrap();
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(reader.nr_frames(), frames.len());
+    for (frame_index, frame) in frames.iter().enumerate() {
+        assert_eq!(&read_frame(&mut reader, frame_index), frame);
+    }
+}
+
+#[test]
+fn truncated_and_corrupted_chunks_are_errors() {
+    let path = trajectory_path("corrupted");
+    let (_, frames) = record(&path, 0);
+    let bytes = std::fs::read(&path).unwrap();
+    let start = chunks_start(&bytes);
+
+    // cut off halfway the chunks: only the complete chunks are left
+    let end = (start + index_offset(&bytes)) / 2;
+    std::fs::write(&path, &bytes[..end]).unwrap();
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    assert!(reader.nr_frames() < frames.len());
+    for (frame_index, frame) in frames.iter().enumerate().take(reader.nr_frames()) {
+        assert_eq!(&read_frame(&mut reader, frame_index), frame);
+    }
+    assert!(reader.frame(reader.nr_frames()).is_err());
+
+    // the first chunk claims to be longer than the file
+    let mut too_long = bytes.clone();
+    too_long[start..start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&path, &too_long).unwrap();
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    assert!(reader.frame(0).is_err());
+
+    // the compressed frames of the first chunk are overwritten
+    let mut garbled = bytes.clone();
+    garbled[start + 4..start + 64].fill(0xFF);
+    std::fs::write(&path, &garbled).unwrap();
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    assert!(reader.frame(0).is_err());
+
+    // a u64 length, then the offset, first frame, first tick and number of frames of every chunk
+    let nr_frames_of_chunk = |chunk: usize| index_offset(&bytes) + 8 + chunk * 28 + 8 + 8 + 4;
+    let with_nr_frames = |chunk: usize, nr_frames: u64| {
+        let mut changed = bytes.clone();
+        let at = nr_frames_of_chunk(chunk);
+        changed[at..at + 8].copy_from_slice(&nr_frames.to_le_bytes());
+        std::fs::write(&path, &changed).unwrap();
+        TrajectoryReader::open(&path).unwrap()
+    };
+    // the index claims more frames for the last chunk than it holds
+    let mut reader = with_nr_frames(1, 20);
+    assert!(reader.frame(frames.len() + 5).is_err());
+    // the chunks of the index don't follow each other: they are scanned instead
+    let mut reader = with_nr_frames(0, 1000);
+    assert_eq!(reader.nr_frames(), frames.len());
+    assert_eq!(read_frame(&mut reader, 40), frames[40]);
+
+    // without the index, the scan stops at the broken chunk
+    std::fs::write(&path, &garbled[..index_offset(&garbled)]).unwrap();
+    let reader = TrajectoryReader::open(&path).unwrap();
+    assert_eq!(reader.nr_frames(), 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+// the index of the last frame at or before the tick, found by going through all frames
+fn last_frame_at_or_before(frames: &Frames, tick: u32) -> usize {
+    frames
+        .iter()
+        .rposition(|frame| frame[0].0 <= tick)
+        .unwrap_or(0)
+}
+
+#[test]
+fn seeks_the_frame_of_any_tick() {
+    let path = trajectory_path("seek");
+    // the first frame is at tick 5, not a multiple of the record interval
+    let (world, frames) = record(&path, 5);
+    let mut reader = TrajectoryReader::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let frame_tick = |frame_index: usize| frames[frame_index][0].0;
+    assert_eq!((frame_tick(0), frame_tick(1), frame_tick(2)), (5, 6, 9));
+
+    // between recorded frames
+    assert_eq!(reader.frame_index_at_tick(7).unwrap(), 1);
+    assert_eq!(reader.frame_index_at_tick(8).unwrap(), 1);
+    assert_eq!(reader.frame_index_at_tick(9).unwrap(), 2);
+    // before the first frame
+    assert_eq!(reader.frame_index_at_tick(0).unwrap(), 0);
+    // past the last frame
+    assert_eq!(
+        reader.frame_index_at_tick(world.tick() + 100).unwrap(),
+        frames.len() - 1
+    );
+    assert_eq!(
+        reader.frame_index_at_tick(u32::MAX).unwrap(),
+        frames.len() - 1
+    );
+
+    // across the chunk boundary at frame 32, and back
+    for tick in frame_tick(30)..=frame_tick(34) + 1 {
+        assert_eq!(
+            reader.frame_index_at_tick(tick).unwrap(),
+            last_frame_at_or_before(&frames, tick),
+            "tick {}",
+            tick
+        );
+    }
+    for tick in (0..=world.tick()).rev() {
+        let frame_index = reader.frame_index_at_tick(tick).unwrap();
+        assert_eq!(frame_index, last_frame_at_or_before(&frames, tick));
+        assert_eq!(read_frame(&mut reader, frame_index), frames[frame_index]);
+    }
+    for frame_index in [40, 3, 33, 31, 32, 0] {
+        assert_eq!(read_frame(&mut reader, frame_index), frames[frame_index]);
+    }
+}